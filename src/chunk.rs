@@ -1,4 +1,3 @@
-
 use crate::value::Value;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -6,6 +5,7 @@ pub enum Op {
     Constant,
     True,
     False,
+    None,
 
     Add,
     Subtract,
@@ -22,24 +22,110 @@ pub enum Op {
     LessEqual,
     Not,
     Negative,
+    Positive,
 
     Noop,
     Pop,
+    /// Pops a value into the global named by the operand.
+    DefineGlobal,
+    GetGlobal,
+    /// Assigns the global named by the operand, leaving the value on the stack.
+    SetGlobal,
+    GetLocal,
+    /// Assigns the local in the operand's slot, leaving the value on the stack.
+    SetLocal,
+    GetUpvalue,
+    /// Stores the top of the stack in an upvalue, leaving it there.
+    SetUpvalue,
+    /// Moves the local on top of the stack into the upvalues capturing it
+    /// and pops it.
+    CloseUpvalue,
+    /// Pushes a copy of the top of the stack.
+    Dup,
+    /// Swaps the top two values.
+    Swap,
+    /// Moves the top value below the next two.
+    Rotate,
+    Jump,
+    /// Jumps backwards by its distance.
+    Loop,
+    /// Pops the top of the stack and jumps if it is false.
+    JumpIfFalse,
+    /// Jumps if the top of the stack is false, otherwise pops it.
+    JumpIfFalseOrPop,
+    /// Jumps if the top of the stack is true, otherwise pops it.
+    JumpIfTrueOrPop,
+    /// Pops its element count of values into a new list.
+    BuildList,
+    /// Replaces the top of the stack with an iterator over it.
+    GetIter,
+    /// Pushes the next value of the iterator on top of the stack, or jumps
+    /// once it is exhausted.
+    ForIter,
+    /// Pops a container and a value, pushing whether the value is in it.
+    Contains,
+    /// Pushes a new class named by its constant.
+    Class,
+    /// Like `Class`, but pops the superclass of the new class first.
+    Subclass,
+    /// Pops a value into the class below it as the attribute named by its
+    /// constant.
+    Method,
+    /// Replaces an object with its attribute named by the constant.
+    GetAttribute,
+    /// Sets the attribute named by the constant of the object below the top
+    /// of the stack, leaving only the value.
+    SetAttribute,
+    /// Replaces the receiver of a `super()` call with the attribute named by
+    /// the constant, looked up from the superclass of the running method's
+    /// class.
+    GetSuper,
+    /// Wraps a function constant in a closure, capturing the variables its
+    /// `Capture` operands list.
+    Closure,
+    Call,
     Return,
 
     Assert,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BytecodeOp {
     Op(Op),
     ConstantIndex(u8),
-    JumpDistance(u8),
+    ArgumentCount(u8),
+    ElementCount(u8),
+    SlotIndex(u8),
+    UpvalueIndex(u8),
+    /// A variable for `Closure` to capture: a local slot of the enclosing
+    /// function, or one of its upvalues.
+    Capture { local: bool, index: u8 },
+    JumpDistance(u16),
 }
 
-#[derive(Debug)]
-pub struct Chunk<'vm> {
+#[derive(Debug, Default)]
+pub struct Chunk {
     pub codes: Vec<BytecodeOp>,
     pub lines: Vec<u16>,
-    pub constants: Vec<Value<'vm>>
-}
\ No newline at end of file
+    pub constants: Vec<Value>
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk { codes: vec![], lines: vec![], constants: vec![] }
+    }
+
+    #[inline]
+    pub fn write(&mut self, code: BytecodeOp, line: usize) {
+        self.codes.push(code);
+        self.lines.push(line as u16);
+    }
+
+    /// Adds `value` to the constant pool, returning its index or `None` if the
+    /// pool no longer fits in a `ConstantIndex`.
+    pub fn add_constant(&mut self, value: Value) -> Option<u8> {
+        let index = u8::try_from(self.constants.len()).ok()?;
+        self.constants.push(value);
+        Some(index)
+    }
+}
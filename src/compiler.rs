@@ -1,15 +1,51 @@
-use crate::{token::Token, parser::Parser, object::ObjFucntion};
+use std::rc::Rc;
 
+use hashbrown::{HashMap, HashSet};
+
+use crate::{token::{Token, Tokenizer, TokenType}, parser::Parser, object::ObjFucntion, error::Diagnostic};
+
+pub type CompileErrors = Vec<Diagnostic>;
 
 #[derive(Debug)]
-struct Local {
-    name: Token,
-    depth: usize,
-    captured: bool,
+pub struct Local {
+    pub name: Token,
+    /// Scope depth of the declaring block, `None` until its initializer has
+    /// been compiled.
+    pub depth: Option<usize>,
+    /// Whether a closure captures it, so it must be closed over when it goes
+    /// out of scope.
+    pub captured: bool,
 }
 
+/// A variable captured by the function being compiled, as `Closure` will
+/// find it in the enclosing function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Upvalue {
+    pub local: bool,
+    pub index: u8,
+}
+
+/// A `global` or `nonlocal` statement about a name in a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Declaration {
+    Global,
+    /// Holds the upvalue index of the name.
+    Nonlocal(u8),
+}
+
+/// A loop being compiled, for `break` and `continue`.
 #[derive(Debug)]
-enum FuncType {
+pub struct Loop {
+    /// Index of the first instruction of the condition.
+    pub start: usize,
+    /// Scope depth outside the loop body.
+    pub depth: usize,
+    /// Jump distances to patch to the end of the loop.
+    pub breaks: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuncType {
     Function,
     Initializer,
     Method,
@@ -17,14 +53,255 @@ enum FuncType {
 }
 
 #[derive(Debug)]
-pub struct Compiler<'vm> {
-    parser: Parser<'vm>,
-    functype: FuncType,
-    function: ObjFucntion<'vm>,
-    scope_depth: usize,
-    locals: Vec<Local>
+pub struct Compiler {
+    pub functype: FuncType,
+    pub function: ObjFucntion,
+    pub scope_depth: usize,
+    pub locals: Vec<Local>,
+    pub upvalues: Vec<Upvalue>,
+    pub loops: Vec<Loop>,
+    pub declarations: HashMap<String, Declaration>,
+}
+
+impl Compiler {
+    pub fn new(functype: FuncType, name: String) -> Compiler {
+        // slot zero belongs to the function being called
+        let callee = Local {
+            name: Token { tokentype: TokenType::Identifier, start: 0, length: 0, line: 0 },
+            depth: Some(0),
+            captured: false,
+        };
+        Compiler {
+            functype,
+            function: ObjFucntion::new(name),
+            scope_depth: 0,
+            locals: vec![callee],
+            upvalues: vec![],
+            loops: vec![],
+            declarations: HashMap::new(),
+        }
+    }
+}
+
+/// Tokenizes and compiles `source` into the top-level script function.
+pub fn compile(source: &str) -> Result<ObjFucntion, CompileErrors> {
+    compile_with(source, &mut HashSet::new())
+}
+
+/// Like `compile`, but interns string constants into `strings`.
+pub fn compile_with(source: &str, strings: &mut HashSet<Rc<str>>) -> Result<ObjFucntion, CompileErrors> {
+    let tokens = Tokenizer::new(source).tokenize();
+    Parser::new(source, tokens, strings).compile()
 }
 
-impl<'a> Compiler<'a> {
-    fn new{}
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use crate::{chunk::{BytecodeOp, Op}, object::Object, value::Value};
+    use super::compile;
+
+    #[test]
+    fn test_compile_arithmetic() {
+        let function = compile("(1 + 2.5) * -3j").unwrap();
+        let truth = vec![
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(0),
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(1),
+            BytecodeOp::Op(Op::Add),
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(2),
+            BytecodeOp::Op(Op::Multiply),
+            BytecodeOp::Op(Op::Return),
+        ];
+        assert_eq!(function.chunk.codes, truth);
+        assert_eq!(function.chunk.lines.len(), truth.len());
+        assert!(matches!(function.chunk.constants[0], Value::Integer(1)));
+        assert!(matches!(function.chunk.constants[1], Value::Float(x) if x == 2.5));
+        assert!(matches!(function.chunk.constants[2], Value::Complex(x) if x.im == -3.0));
+    }
+
+    #[test]
+    fn test_compile_chained_comparison() {
+        let function = compile("1 < 2 <= 3").unwrap();
+        let truth = vec![
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(0),
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(1),
+            BytecodeOp::Op(Op::Dup),
+            BytecodeOp::Op(Op::Rotate),
+            BytecodeOp::Op(Op::Less),
+            BytecodeOp::Op(Op::JumpIfFalseOrPop), BytecodeOp::JumpDistance(5),
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(2),
+            BytecodeOp::Op(Op::LessEqual),
+            BytecodeOp::Op(Op::Jump), BytecodeOp::JumpDistance(2),
+            BytecodeOp::Op(Op::Swap),
+            BytecodeOp::Op(Op::Pop),
+            BytecodeOp::Op(Op::Return),
+        ];
+        assert_eq!(function.chunk.codes, truth);
+    }
+
+    #[test]
+    fn test_compile_folds_negative_literals() {
+        let function = compile("-2147483648").unwrap();
+        assert_eq!(function.chunk.codes, vec![BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(0), BytecodeOp::Op(Op::Return)]);
+        assert!(matches!(function.chunk.constants[0], Value::Integer(i32::MIN)));
+
+        let function = compile("-'a'").unwrap();
+        assert_eq!(function.chunk.codes[2], BytecodeOp::Op(Op::Negative));
+    }
+
+    #[test]
+    fn test_compile_assignment() {
+        let function = compile("x = 1\ny = x = x").unwrap();
+        let truth = vec![
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(0),
+            BytecodeOp::Op(Op::DefineGlobal), BytecodeOp::ConstantIndex(1),
+            BytecodeOp::Op(Op::GetGlobal), BytecodeOp::ConstantIndex(1),
+            BytecodeOp::Op(Op::SetGlobal), BytecodeOp::ConstantIndex(2),
+            BytecodeOp::Op(Op::DefineGlobal), BytecodeOp::ConstantIndex(1),
+            BytecodeOp::Op(Op::None),
+            BytecodeOp::Op(Op::Return),
+        ];
+        assert_eq!(function.chunk.codes, truth);
+        assert_eq!(function.chunk.constants.len(), 3);
+
+        let errors = compile("(x) = 1\nx + 1 = 2").unwrap_err();
+        assert!(errors.iter().all(|e| e.message == "invalid assignment target"));
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_compile_implicit_return() {
+        let function = compile("def f(): pass\nclass A:\n    def __init__(self): pass\n").unwrap();
+        let bodies: Vec<Vec<BytecodeOp>> = function.chunk.constants.iter().filter_map(|constant| match constant {
+            Value::Object(Object::Function(function)) => Some(function.chunk.codes.clone()),
+            Value::Object(Object::Class(_)) | Value::Object(Object::String(_)) => None,
+            constant => panic!("unexpected constant {}", constant),
+        }).collect();
+        assert_eq!(bodies, vec![
+            vec![BytecodeOp::Op(Op::None), BytecodeOp::Op(Op::Return)],
+            vec![BytecodeOp::Op(Op::GetLocal), BytecodeOp::SlotIndex(0), BytecodeOp::Op(Op::Return)],
+        ]);
+    }
+
+    #[test]
+    fn test_compile_big_integer() {
+        let function = compile("99999999999 + 2_147_483_647").unwrap();
+        assert!(matches!(&function.chunk.constants[0], Value::BigInt(x) if x.to_string() == "99999999999"));
+        assert!(matches!(function.chunk.constants[1], Value::Integer(i32::MAX)));
+    }
+
+    #[test]
+    fn test_compile_reports_every_error() {
+        let errors = compile("assert (1 + )\nassert 2 * )\nassert 3 +").unwrap_err();
+        let found: Vec<(usize, usize, &str)> = errors.iter().map(|e| (e.line, e.column, e.lexeme.as_str())).collect();
+        assert_eq!(found, vec![(1, 13, ")"), (2, 12, ")"), (3, 11, "")]);
+        assert_eq!(errors[1].message, "expected expression");
+        assert_eq!(errors[1].to_string(), "[line 2, column 12] Error at ')': expected expression");
+    }
+
+    #[test]
+    fn test_compile_reports_lexical_errors() {
+        let errors = compile("1 @ 2\n$ + 3").unwrap_err();
+        let found: Vec<(usize, usize, &str)> = errors.iter().map(|e| (e.line, e.column, e.lexeme.as_str())).collect();
+        assert_eq!(found, vec![(1, 3, "@"), (2, 1, "$")]);
+        assert_eq!(errors[0].message, "unexpected character");
+    }
+
+    #[test]
+    fn test_compile_statements_end_at_newline() {
+        let errors = compile("1 2\n  3\n4 +\n").unwrap_err();
+        let found: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.message.as_str())).collect();
+        assert_eq!(found, vec![(1, "expected newline after statement"), (2, "unexpected indent"), (3, "expected expression")]);
+    }
+
+    #[test]
+    fn test_compile_conditional_errors() {
+        let errors = compile("if 1\n    2\nelse 3\nx = 1 if 2\n").unwrap_err();
+        let found: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.message.as_str())).collect();
+        assert_eq!(found, vec![
+            (1, "expected ':' after condition"),
+            (3, "expected ':' after 'else'"),
+            (4, "expected 'else' after condition"),
+        ]);
+    }
+
+    #[test]
+    fn test_compile_loop_errors() {
+        let errors = compile("break\nif 1:\n    continue\nwhile 1: break 2\n").unwrap_err();
+        let found: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.message.as_str())).collect();
+        assert_eq!(found, vec![
+            (1, "'break' outside loop"),
+            (3, "'continue' not properly in loop"),
+            (4, "expected newline after statement"),
+        ]);
+    }
+
+    #[test]
+    fn test_compile_function_errors() {
+        let errors = compile("return 1\ndef f(a, a): pass\ndef g(a b): pass\ndef h():\nh()\n").unwrap_err();
+        let found: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.message.as_str())).collect();
+        assert_eq!(found, vec![
+            (1, "'return' outside function"),
+            (2, "duplicate argument 'a' in function definition"),
+            (3, "expected ')' after parameters"),
+            (5, "expected an indented block"),
+        ]);
+    }
+
+    #[test]
+    fn test_compile_upvalue_limit() {
+        let source = |middle: usize| {
+            let outer: Vec<String> = (0..200).map(|i| format!("a{}", i)).collect();
+            let inner: Vec<String> = (0..middle).map(|i| format!("b{}", i)).collect();
+            let assign = |names: &[String], indent: &str| names.iter().map(|name| format!("{}{} = 0\n", indent, name)).collect::<String>();
+            format!(
+                "def outer():\n{}    def middle():\n{}        def inner():\n            return [{}]\n",
+                assign(&outer, "    "),
+                assign(&inner, "        "),
+                [outer.clone(), inner.clone()].concat().join(", "),
+            )
+        };
+        assert!(compile(&source(55)).is_ok());
+
+        let errors = compile(&source(56)).unwrap_err();
+        let found: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.message.as_str())).collect();
+        assert_eq!(found, vec![(260, "too many closure variables in function")]);
+    }
+
+    #[test]
+    fn test_compile_scope_errors() {
+        let source = "nonlocal x\ndef f():\n    nonlocal y\n    z = 1\n    global z\n    w = 1\n    def g():\n        global w\n        nonlocal w\n";
+        let errors = compile(source).unwrap_err();
+        let found: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.message.as_str())).collect();
+        assert_eq!(found, vec![
+            (1, "nonlocal declaration not allowed at module level"),
+            (3, "no binding for nonlocal 'y' found"),
+            (5, "name 'z' is assigned to before global declaration"),
+            (9, "name 'w' is nonlocal and global"),
+        ]);
+        assert!(compile("global x\nx = 1\n").is_ok());
+    }
+
+    #[test]
+    fn test_compile_class_errors() {
+        let errors = compile("class A:\n    1 + 2\n    def __init__(self):\n        return 1\n    def f(self): return 1\nclass B\n").unwrap_err();
+        let found: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.message.as_str())).collect();
+        assert_eq!(found, vec![
+            (2, "expected a method or attribute in class body"),
+            (4, "cannot return a value from __init__"),
+            (6, "expected ':' after class name"),
+        ]);
+
+        let errors = compile("def f(self): return super().f()\nclass A:\n    def g(): return super().g()\n    def h(self): return super.h\n").unwrap_err();
+        let found: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.message.as_str())).collect();
+        assert_eq!(found, vec![
+            (1, "super() must be called directly in a method with a 'self' parameter"),
+            (3, "super() must be called directly in a method with a 'self' parameter"),
+        ]);
+    }
+
+    #[test]
+    fn test_compile_error_at_end() {
+        let errors = compile("(1 + 2").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "[line 1, column 7] Error at end of line: expected ')' after expression");
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Assertion,
+    Attribute,
+    Internal,
+    Name,
+    Overflow,
+    Recursion,
+    Type,
+    Value,
+    ZeroDivision,
+}
+
+impl From<ErrorKind> for &str {
+    fn from(value: ErrorKind) -> Self {
+        match value {
+            ErrorKind::Assertion => "AssertionError",
+            ErrorKind::Attribute => "AttributeError",
+            ErrorKind::Internal => "InternalError",
+            ErrorKind::Name => "NameError",
+            ErrorKind::Overflow => "OverflowError",
+            ErrorKind::Recursion => "RecursionError",
+            ErrorKind::Type => "TypeError",
+            ErrorKind::Value => "ValueError",
+            ErrorKind::ZeroDivision => "ZeroDivisionError",
+        }
+    }
+}
+
+/// One entry of a traceback: the function that was executing and the source
+/// line of the instruction it was on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceFrame {
+    pub function: String,
+    /// 1-based, as printed.
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Outermost call first, like Python's "most recent call last".
+    pub traceback: Vec<TraceFrame>,
+}
+
+impl RuntimeError {
+    /// Creates an error without a traceback; the VM fills it in from its
+    /// call frames when the error escapes `run`.
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> RuntimeError {
+        RuntimeError { kind, message: message.into(), traceback: vec![] }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.traceback.is_empty() {
+            writeln!(f, "Traceback (most recent call last):")?;
+            for frame in self.traceback.iter() {
+                writeln!(f, "  line {}, in {}", frame.line, frame.function)?;
+            }
+        }
+
+        let kind: &str = self.kind.into();
+        if self.message.is_empty() {
+            write!(f, "{}", kind)
+        } else {
+            write!(f, "{}: {}", kind, self.message)
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// A compile-time problem, located by the offending token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// 1-based, as printed.
+    pub line: usize,
+    /// 1-based character offset into the line.
+    pub column: usize,
+    /// Source text of the offending token, empty at the end of a line.
+    pub lexeme: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[line {}, column {}] Error", self.line, self.column)?;
+        if self.lexeme.is_empty() {
+            write!(f, " at end of line")?;
+        } else {
+            write!(f, " at '{}'", self.lexeme)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
@@ -0,0 +1,12 @@
+pub mod token;
+pub mod parser;
+pub mod chunk;
+pub mod value;
+pub mod object;
+pub mod compiler;
+pub mod vm;
+pub mod error;
+pub mod native;
+
+pub use compiler::{compile, compile_with};
+pub use vm::{VM, InterpretResult};
@@ -1,26 +1,53 @@
+use std::{env, fs, io::{self, BufRead, Write}, process};
 
-mod token;
-mod parser;
-mod chunk;
-mod value;
-mod object;
-mod compiler;
-mod vm;
+use slug3::{VM, InterpretResult, value::Value};
 
-use vm::VM;
-use parser::Parser;
-use token::Tokenizer;
-use compiler::Compiler;
+fn report(result: InterpretResult) -> i32 {
+    match result {
+        InterpretResult::Ok(value) => {
+            if !matches!(value, Value::None) {
+                println!("{}", value);
+            }
+            0
+        }
+        InterpretResult::CompileError(errors) => {
+            for error in errors.iter() {
+                eprintln!("{}", error);
+            }
+            65
+        }
+        InterpretResult::RuntimeError(error) => {
+            eprintln!("{}", error);
+            70
+        }
+    }
+}
 
-fn main() {
+fn repl(vm: &mut VM) {
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
 
-    let vm: VM = VM::new();
-    let compiler: Compiler::new()
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => _ = report(vm.interpret(&line)),
+        }
+    }
+}
+
+fn main() {
+    let mut vm = VM::new();
 
-    // let tokens = Tokenizer::new(r"1 + 3 == 4").tokenize();
-    // for t in tokens.iter() {
-    //     println!("\t{:?}", t.tokentype);
-    // }
-    // let parser = &mut Parser::new(tokens);
-    // parser.declaration()
+    match env::args().nth(1) {
+        None => repl(&mut vm),
+        Some(path) => {
+            let source = fs::read_to_string(&path).unwrap_or_else(|err| {
+                eprintln!("could not read {}: {}", path, err);
+                process::exit(74);
+            });
+            process::exit(report(vm.interpret(&source)));
+        }
+    }
 }
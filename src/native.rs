@@ -0,0 +1,53 @@
+use std::rc::Rc;
+
+use num::{BigInt, Zero};
+
+use crate::{object::{ObjNative, ObjRange, Object}, value::Value, error::{ErrorKind, RuntimeError}};
+
+/// Every builtin function, defined as a global by `VM::new`.
+pub fn builtins() -> Vec<ObjNative> {
+    vec![
+        ObjNative { name: "fraction", arity: 2, optional: 0, function: fraction },
+        ObjNative { name: "len", arity: 1, optional: 0, function: len },
+        ObjNative { name: "range", arity: 3, optional: 2, function: range },
+    ]
+}
+
+fn len(args: &[Value]) -> Result<Value, RuntimeError> {
+    let len = match &args[0] {
+        Value::Object(Object::String(x)) => x.chars().count(),
+        Value::Object(Object::List(x)) => x.borrow().len(),
+        Value::Object(Object::Range(x)) => x.len(),
+        x => return Err(RuntimeError::new(ErrorKind::Type, format!("object of type '{}' has no len()", x.type_name()))),
+    };
+    Ok(Value::from(BigInt::from(len)))
+}
+
+fn fraction(args: &[Value]) -> Result<Value, RuntimeError> {
+    match (args[0].to_rational(), args[1].to_rational()) {
+        (Some(_), Some(denominator)) if denominator.is_zero() => Err(RuntimeError::new(ErrorKind::ZeroDivision, format!("Fraction({}, 0)", args[0]))),
+        (Some(numerator), Some(denominator)) => Ok(Value::from(numerator / denominator)),
+        _ => Err(RuntimeError::new(ErrorKind::Type, "both arguments should be Rational instances")),
+    }
+}
+
+/// `range(stop)`, `range(start, stop)` or `range(start, stop, step)`.
+fn range(args: &[Value]) -> Result<Value, RuntimeError> {
+    let args = args.iter().map(|arg| match arg {
+        Value::Bool(x) => Ok(i32::from(*x)),
+        Value::Integer(x) => Ok(*x),
+        Value::BigInt(_) => Err(RuntimeError::new(ErrorKind::Overflow, "Python int too large to convert to C long")),
+        x => Err(RuntimeError::new(ErrorKind::Type, format!("'{}' object cannot be interpreted as an integer", x.type_name()))),
+    }).collect::<Result<Vec<i32>, RuntimeError>>()?;
+
+    let (start, stop, step) = match args[..] {
+        [stop] => (0, stop, 1),
+        [start, stop] => (start, stop, 1),
+        [start, stop, step] => (start, stop, step),
+        _ => unreachable!("range takes 1 to 3 arguments"),
+    };
+    if step == 0 {
+        return Err(RuntimeError::new(ErrorKind::Value, "range() arg 3 must not be zero"));
+    }
+    Ok(Value::Object(Object::Range(Rc::new(ObjRange { start, stop, step }))))
+}
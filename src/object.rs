@@ -1,28 +1,307 @@
+use std::{cell::RefCell, fmt, rc::Rc};
 
-use crate::chunk::Chunk;
+use hashbrown::{HashMap, HashSet};
+use num::ToPrimitive;
 
-#[derive(Debug)]
+use crate::{chunk::Chunk, value::Value, error::RuntimeError};
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ObjectType {
-    // OBJ_BOUND_METHOD,
-    // OBJ_CLASS,
-    // OBJ_CLOSURE,
+    OBJ_BOUND_METHOD,
+    OBJ_CLASS,
+    OBJ_CLOSURE,
     OBJ_FUNCTION,
-    // OBJ_INSTANCE,
+    OBJ_INSTANCE,
+    OBJ_ITERATOR,
+    OBJ_LIST,
     OBJ_NATIVE,
-    // OBJ_STRING,
+    OBJ_RANGE,
+    OBJ_SENTINEL,
+    OBJ_STRING,
     // OBJ_UPVALUE,
 }
-#[derive(Debug)]
-pub struct Object {
-    pub objecttype: ObjectType,
-    pub marked: bool
+
+impl From<ObjectType> for &str {
+    fn from(value: ObjectType) -> Self {
+        match value {
+            ObjectType::OBJ_BOUND_METHOD => "method",
+            ObjectType::OBJ_CLASS => "type",
+            ObjectType::OBJ_CLOSURE | ObjectType::OBJ_FUNCTION => "function",
+            // the type of an instance is its class, this is only a fallback
+            ObjectType::OBJ_INSTANCE => "object",
+            ObjectType::OBJ_ITERATOR => "iterator",
+            ObjectType::OBJ_LIST => "list",
+            ObjectType::OBJ_NATIVE => "builtin_function_or_method",
+            ObjectType::OBJ_RANGE => "range",
+            // each sentinel names its own type, this is only a fallback
+            ObjectType::OBJ_SENTINEL => "object",
+            ObjectType::OBJ_STRING => "str",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Object {
+    BoundMethod(Rc<ObjBoundMethod>),
+    Class(Rc<ObjClass>),
+    Closure(Rc<ObjClosure>),
+    Function(Rc<ObjFucntion>),
+    Instance(Rc<ObjInstance>),
+    Iterator(Rc<RefCell<ObjIterator>>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Native(Rc<ObjNative>),
+    Range(Rc<ObjRange>),
+    Sentinel(Sentinel),
+    String(Rc<str>),
+}
+
+impl Object {
+    pub fn objecttype(&self) -> ObjectType {
+        match self {
+            Object::BoundMethod(_) => ObjectType::OBJ_BOUND_METHOD,
+            Object::Class(_) => ObjectType::OBJ_CLASS,
+            Object::Closure(_) => ObjectType::OBJ_CLOSURE,
+            Object::Function(_) => ObjectType::OBJ_FUNCTION,
+            Object::Instance(_) => ObjectType::OBJ_INSTANCE,
+            Object::Iterator(_) => ObjectType::OBJ_ITERATOR,
+            Object::List(_) => ObjectType::OBJ_LIST,
+            Object::Native(_) => ObjectType::OBJ_NATIVE,
+            Object::Range(_) => ObjectType::OBJ_RANGE,
+            Object::Sentinel(_) => ObjectType::OBJ_SENTINEL,
+            Object::String(_) => ObjectType::OBJ_STRING,
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::BoundMethod(x) => write!(f, "<bound method {} of {}>", x.method.function.name, x.receiver),
+            Object::Class(x) => write!(f, "<class '{}'>", x.name),
+            Object::Closure(x) => write!(f, "<function {}>", x.function.name),
+            Object::Function(x) => write!(f, "<function {}>", x.name),
+            Object::Instance(x) => write!(f, "<{} object>", x.class.name),
+            Object::Iterator(_) => write!(f, "<iterator object>"),
+            Object::List(x) => {
+                let items: Vec<String> = x.borrow().iter().map(Value::repr).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Object::Native(x) => write!(f, "<built-in function {}>", x.name),
+            Object::Range(x) if x.step == 1 => write!(f, "range({}, {})", x.start, x.stop),
+            Object::Range(x) => write!(f, "range({}, {}, {})", x.start, x.stop, x.step),
+            Object::Sentinel(x) => write!(f, "{}", x.name()),
+            Object::String(x) => write!(f, "{}", x),
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct ObjFucntion<'vm> {
-    pub obj: Object,
+pub struct ObjFucntion {
     pub arity: u8,
-    // upvalue_count: u8,
-    pub chunk: &'vm Chunk<'vm>,
+    pub upvalue_count: u8,
+    pub chunk: Chunk,
     pub name: String
-}
\ No newline at end of file
+}
+
+impl ObjFucntion {
+    pub fn new(name: String) -> ObjFucntion {
+        ObjFucntion { arity: 0, upvalue_count: 0, chunk: Chunk::new(), name }
+    }
+}
+
+/// A function together with the variables it captured from enclosing
+/// functions.
+#[derive(Debug)]
+pub struct ObjClosure {
+    pub function: Rc<ObjFucntion>,
+    pub upvalues: Vec<Rc<RefCell<ObjUpvalue>>>,
+    /// The class a method was defined in, where `super()` starts from.
+    pub class: Option<Rc<ObjClass>>,
+}
+
+/// A captured variable, which stays on the stack until the scope that owns
+/// it ends.
+#[derive(Debug)]
+pub enum ObjUpvalue {
+    /// Index of the variable on the VM stack.
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct ObjClass {
+    pub name: Rc<str>,
+    pub superclass: Option<Rc<ObjClass>>,
+    /// The methods and other attributes assigned in the class body.
+    pub attributes: RefCell<HashMap<Rc<str>, Value>>,
+}
+
+impl ObjClass {
+    pub fn new(name: Rc<str>, superclass: Option<Rc<ObjClass>>) -> ObjClass {
+        ObjClass { name, superclass, attributes: RefCell::new(HashMap::new()) }
+    }
+
+    /// Looks up an attribute in this class, then up its superclasses.
+    pub fn find(&self, name: &str) -> Option<Value> {
+        let mut class = self;
+        loop {
+            if let Some(value) = class.attributes.borrow().get(name) {
+                return Some(value.clone());
+            }
+            class = class.superclass.as_deref()?;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ObjInstance {
+    pub class: Rc<ObjClass>,
+    pub fields: RefCell<HashMap<Rc<str>, Value>>,
+}
+
+impl ObjInstance {
+    pub fn new(class: Rc<ObjClass>) -> ObjInstance {
+        ObjInstance { class, fields: RefCell::new(HashMap::new()) }
+    }
+}
+
+/// A method looked up on an instance, which passes the instance as the first
+/// argument when called.
+#[derive(Debug)]
+pub struct ObjBoundMethod {
+    pub receiver: Value,
+    pub method: Rc<ObjClosure>,
+}
+
+pub type NativeFn = fn(&[Value]) -> Result<Value, RuntimeError>;
+
+#[derive(Debug)]
+pub struct ObjNative {
+    pub name: &'static str,
+    pub arity: u8,
+    /// How many of the trailing arguments may be left out.
+    pub optional: u8,
+    pub function: NativeFn,
+}
+
+/// The integers from `start` up to but excluding `stop`, `step` apart.
+#[derive(Debug)]
+pub struct ObjRange {
+    pub start: i32,
+    pub stop: i32,
+    pub step: i32,
+}
+
+impl ObjRange {
+    pub fn len(&self) -> usize {
+        let (start, stop, step) = (i64::from(self.start), i64::from(self.stop), i64::from(self.step));
+        let len = if step > 0 { (stop - start + step - 1) / step } else { (start - stop - step - 1) / -step };
+        len.max(0) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(&self, index: usize) -> i32 {
+        (i64::from(self.start) + index as i64 * i64::from(self.step)) as i32
+    }
+
+    /// Whether `item` is equal to one of the integers, without visiting them.
+    pub fn contains(&self, item: &Value) -> bool {
+        let integer = match item {
+            Value::Bool(x) => i64::from(*x),
+            Value::Integer(x) => i64::from(*x),
+            Value::Float(x) if x.fract() == 0.0 && x.abs() < 1e18 => *x as i64,
+            Value::Complex(x) if x.im == 0.0 && x.re.fract() == 0.0 && x.re.abs() < 1e18 => x.re as i64,
+            Value::Rational(x) if x.is_integer() => match x.to_integer().to_i64() {
+                Some(x) => x,
+                None => return false,
+            },
+            _ => return false,
+        };
+
+        let offset = integer - i64::from(self.start);
+        let step = i64::from(self.step);
+        offset % step == 0 && (0..self.len() as i64).contains(&(offset / step))
+    }
+}
+
+/// Builtin values that special methods return to signal the VM, since there
+/// are no exceptions to raise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sentinel {
+    /// Returned by a binary operator's special method to let the other
+    /// operand's method or the builtin operator answer instead.
+    NotImplemented,
+    /// Returned by `__next__` when an iterator is exhausted.
+    StopIteration,
+}
+
+impl Sentinel {
+    pub const ALL: [Sentinel; 2] = [Sentinel::NotImplemented, Sentinel::StopIteration];
+
+    /// The name of the builtin global holding the sentinel.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sentinel::NotImplemented => "NotImplemented",
+            Sentinel::StopIteration => "StopIteration",
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Sentinel::NotImplemented => "NotImplementedType",
+            // a class in Python, where it is raised rather than returned
+            Sentinel::StopIteration => "type",
+        }
+    }
+}
+
+/// The state of a `for` loop over a builtin sequence.
+#[derive(Debug)]
+pub enum ObjIterator {
+    List { list: Rc<RefCell<Vec<Value>>>, index: usize },
+    Range { range: Rc<ObjRange>, index: usize },
+    /// `index` is a byte offset to the next character.
+    String { string: Rc<str>, index: usize },
+}
+
+impl Iterator for ObjIterator {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        match self {
+            ObjIterator::List { list, index } => {
+                let item = list.borrow().get(*index).cloned()?;
+                *index += 1;
+                Some(item)
+            }
+            ObjIterator::Range { range, index } => {
+                if *index >= range.len() {
+                    return None;
+                }
+                *index += 1;
+                Some(Value::Integer(range.get(*index - 1)))
+            }
+            ObjIterator::String { string, index } => {
+                let chr = string[*index..].chars().next()?;
+                *index += chr.len_utf8();
+                Some(Value::string(chr.to_string()))
+            }
+        }
+    }
+}
+
+/// Returns the shared copy of `string`, adding it to `strings` if it is new.
+pub fn intern(strings: &mut HashSet<Rc<str>>, string: &str) -> Rc<str> {
+    match strings.get(string) {
+        Some(interned) => interned.clone(),
+        None => {
+            let interned: Rc<str> = Rc::from(string);
+            strings.insert(interned.clone());
+            interned
+        }
+    }
+}
@@ -1,18 +1,23 @@
+use std::rc::Rc;
 
-use crate::{token::{Token, TokenType, Number, Operator, Keyword}, chunk::Op, compiler::Compiler};
+use hashbrown::HashSet;
+use num::{complex::Complex, BigInt};
+
+use crate::{token::{Token, TokenType, Number, Operator, Keyword}, chunk::{Op, BytecodeOp, Chunk}, compiler::{Compiler, CompileErrors, Declaration, FuncType, Local, Loop, Upvalue}, object::{ObjFucntion, Object, intern}, value::{Value, is_number}, error::Diagnostic};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Precedence {
     None,
     Assignment,
+    Conditional,
     Or,
     And,
-    Equality,
+    Not,
     Comparison,
     Term,
     Factor,
-    Exponent,
     Unary,
+    Exponent,
     Call,
     Primary
 }
@@ -21,15 +26,16 @@ impl Precedence {
     fn next(&self) -> Self {
         match self {
             Precedence::None => Precedence::Assignment,
-            Precedence::Assignment => Precedence::Or,
+            Precedence::Assignment => Precedence::Conditional,
+            Precedence::Conditional => Precedence::Or,
             Precedence::Or => Precedence::And,
-            Precedence::And => Precedence::Equality,
-            Precedence::Equality => Precedence::Comparison,
+            Precedence::And => Precedence::Not,
+            Precedence::Not => Precedence::Comparison,
             Precedence::Comparison => Precedence::Term,
             Precedence::Term => Precedence::Factor,
-            Precedence::Factor => Precedence::Exponent,
-            Precedence::Exponent => Precedence::Unary,
-            Precedence::Unary => Precedence::Call,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Exponent,
+            Precedence::Exponent => Precedence::Call,
             Precedence::Call => Precedence::Primary,
             Precedence::Primary => panic!("no next precedence"),
         }
@@ -41,22 +47,26 @@ impl From<TokenType> for ParseRule {
         match val {
             TokenType::Operator(Operator::LeftParen) => ParseRule { prefix: Some(grouping), infix: Some(call), precedence: Precedence::Call },
             TokenType::Operator(Operator::RightParen) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Operator(Operator::LeftBracket) => ParseRule { prefix: Some(list), infix: None, precedence: Precedence::None },
+            TokenType::Operator(Operator::RightBracket) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Operator(Operator::Dot) => ParseRule { prefix: None, infix: Some(dot), precedence: Precedence::Call },
 
-            TokenType::Operator(Operator::Plus) => ParseRule { prefix: None, infix: Some(binary), precedence: Precedence::Term },
+            TokenType::Operator(Operator::Plus) => ParseRule { prefix: Some(unary), infix: Some(binary), precedence: Precedence::Term },
             TokenType::Operator(Operator::Minus) => ParseRule { prefix: Some(unary), infix: Some(binary), precedence: Precedence::Term },
             TokenType::Operator(Operator::Star) => ParseRule { prefix: None, infix: Some(binary), precedence: Precedence::Factor },
             TokenType::Operator(Operator::Slash) => ParseRule { prefix: None, infix: Some(binary), precedence: Precedence::Factor },
             TokenType::Operator(Operator::SlashSlash) => ParseRule { prefix: None, infix: Some(binary), precedence: Precedence::Factor },
             TokenType::Operator(Operator::StarStar) => ParseRule { prefix: None, infix: Some(binary), precedence: Precedence::Exponent },
-            
-            TokenType::Operator(Operator::Greater) => ParseRule { prefix: None, infix: Some(binary), precedence: Precedence::Comparison },
-            TokenType::Operator(Operator::GreaterEqual) => ParseRule { prefix: None, infix: Some(binary), precedence: Precedence::Comparison },
-            TokenType::Operator(Operator::Less) => ParseRule { prefix: None, infix: Some(binary), precedence: Precedence::Comparison },
-            TokenType::Operator(Operator::LessEqual) => ParseRule { prefix: None, infix: Some(binary), precedence: Precedence::Comparison },
-            TokenType::Operator(Operator::EqualEqual) => ParseRule { prefix: None, infix: Some(binary), precedence: Precedence::Equality },
-            TokenType::Operator(Operator::NotEqual) => ParseRule { prefix: None, infix: Some(binary), precedence: Precedence::Equality },
-
-            TokenType::Operator(Operator::Pound) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+
+            TokenType::Operator(Operator::Greater) => ParseRule { prefix: None, infix: Some(comparison), precedence: Precedence::Comparison },
+            TokenType::Operator(Operator::GreaterEqual) => ParseRule { prefix: None, infix: Some(comparison), precedence: Precedence::Comparison },
+            TokenType::Operator(Operator::Less) => ParseRule { prefix: None, infix: Some(comparison), precedence: Precedence::Comparison },
+            TokenType::Operator(Operator::LessEqual) => ParseRule { prefix: None, infix: Some(comparison), precedence: Precedence::Comparison },
+            TokenType::Operator(Operator::EqualEqual) => ParseRule { prefix: None, infix: Some(comparison), precedence: Precedence::Comparison },
+            TokenType::Operator(Operator::NotEqual) => ParseRule { prefix: None, infix: Some(comparison), precedence: Precedence::Comparison },
+
+            TokenType::Operator(Operator::Colon) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Operator(Operator::Comma) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
             TokenType::Operator(Operator::Equal) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
 
             TokenType::Number(Number::Integer) => ParseRule { prefix: Some(integer), infix: None, precedence: Precedence::None },
@@ -64,16 +74,34 @@ impl From<TokenType> for ParseRule {
             TokenType::Number(Number::Complex) => ParseRule { prefix: Some(complex), infix: None, precedence: Precedence::None },
 
             TokenType::Keyword(Keyword::Assert) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-            TokenType::Keyword(Keyword::If) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-            TokenType::Keyword(Keyword::In) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-            TokenType::Keyword(Keyword::Not) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Keyword(Keyword::If) => ParseRule { prefix: None, infix: Some(conditional), precedence: Precedence::Conditional },
+            TokenType::Keyword(Keyword::In) => ParseRule { prefix: None, infix: Some(comparison), precedence: Precedence::Comparison },
+            // infix only as the start of `not in`
+            TokenType::Keyword(Keyword::Not) => ParseRule { prefix: Some(not), infix: Some(comparison), precedence: Precedence::Comparison },
             TokenType::Keyword(Keyword::Else) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
             TokenType::Keyword(Keyword::Elif) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
             TokenType::Keyword(Keyword::While) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
             TokenType::Keyword(Keyword::For) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
             TokenType::Keyword(Keyword::Class) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Keyword(Keyword::True) => ParseRule { prefix: Some(literal), infix: None, precedence: Precedence::None },
+            TokenType::Keyword(Keyword::False) => ParseRule { prefix: Some(literal), infix: None, precedence: Precedence::None },
+            TokenType::Keyword(Keyword::None) => ParseRule { prefix: Some(literal), infix: None, precedence: Precedence::None },
+            TokenType::Keyword(Keyword::And) => ParseRule { prefix: None, infix: Some(and), precedence: Precedence::And },
+            TokenType::Keyword(Keyword::Or) => ParseRule { prefix: None, infix: Some(or), precedence: Precedence::Or },
+            TokenType::Keyword(Keyword::Pass) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Keyword(Keyword::Break) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Keyword(Keyword::Continue) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Keyword(Keyword::Def) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Keyword(Keyword::Return) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Keyword(Keyword::Global) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Keyword(Keyword::Nonlocal) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
 
-            TokenType::Identifier => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Identifier => ParseRule { prefix: Some(variable), infix: None, precedence: Precedence::None },
+            TokenType::String => ParseRule { prefix: Some(string), infix: None, precedence: Precedence::None },
+            TokenType::Newline => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Indent => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Dedent => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+            TokenType::Error(_) => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
             TokenType::Eof => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
         }
     }
@@ -88,82 +116,309 @@ struct ParseRule {
     precedence: Precedence
 }
 
-#[derive(Debug)]
-enum FuncType {
-    Function,
-    Initializer,
-    Method,
-    Script,
+/// Where an assignment stores its value.
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Global,
+    Local(u8),
+    /// A local declared by the assignment itself.
+    NewLocal(u8),
+    Upvalue(u8),
 }
 
 #[derive(Debug)]
-pub struct Parser<'vm> {
-    compiler: &'vm Compiler<'vm>,
+pub struct Parser<'strings> {
+    compilers: Vec<Compiler>,
+    strings: &'strings mut HashSet<Rc<str>>,
+    chars: Vec<char>,
+    previous_index: usize,
     current_index: usize,
     tokens: Vec<Token>,
+    errors: CompileErrors,
+    result_pop: Option<usize>,
+    /// Where the code of the left operand of the infix being parsed starts.
+    operand_start: usize,
     had_error: bool,
     panic_mode: bool
 }
 
-impl<'vm> Parser<'vm> {
-    pub fn new(compiler: &'vm Compiler<'vm>, tokens: Vec<Token>) -> Parser<'vm> {
-        Parser { compiler, current_index: 0, tokens, had_error: false, panic_mode: false }
+impl<'strings> Parser<'strings> {
+    pub fn new(source: &str, tokens: Vec<Token>, strings: &'strings mut HashSet<Rc<str>>) -> Parser<'strings> {
+        let compilers = vec![Compiler::new(FuncType::Script, String::from("<script>"))];
+        let chars = source.chars().collect();
+        let mut parser = Parser { compilers, strings, chars, previous_index: 0, current_index: 0, tokens, errors: vec![], result_pop: None, operand_start: 0, had_error: false, panic_mode: false };
+        parser.skip_lexical_errors();
+        parser
+    }
+
+    /// Compiles every declaration up to `Eof` into the top-level script function,
+    /// which returns the value of its final expression statement.
+    pub fn compile(mut self) -> Result<ObjFucntion, CompileErrors> {
+        while !self.match_tokentype(TokenType::Eof) {
+            self.declaration();
+        }
+
+        let result_pop = self.result_pop;
+        let chunk = self.current_chunk();
+        if result_pop.is_some_and(|index| index + 1 == chunk.codes.len()) {
+            chunk.codes.pop();
+            chunk.lines.pop();
+        } else {
+            self.emit_byte(Op::None, self.previous().line);
+        }
+
+        let function = self.end_compiler();
+        if self.had_error {
+            Err(self.errors)
+        } else {
+            Ok(function)
+        }
     }
 
     #[inline]
     fn current(&self) -> Token {
-        *self.tokens.get(self.current_index).unwrap()
+        // everything past the end reads as the trailing `Eof`
+        *self.tokens.get(self.current_index).unwrap_or(self.tokens.last().unwrap())
+    }
+
+    /// The token after `current`, for the one place that needs two tokens of
+    /// lookahead.
+    #[inline]
+    fn next(&self) -> Token {
+        *self.tokens.get(self.current_index + 1).unwrap_or(self.tokens.last().unwrap())
     }
 
     #[inline]
     fn previous(&self) -> Token {
-        *self.tokens.get(self.current_index - 1).unwrap()
+        *self.tokens.get(self.previous_index).unwrap_or(self.tokens.last().unwrap())
+    }
+
+    #[inline]
+    fn lexeme(&self, token: Token) -> String {
+        self.chars[token.start..token.start + token.length].iter().collect()
+    }
+
+    #[inline]
+    fn advance(&mut self) {
+        self.previous_index = self.current_index;
+        self.current_index += 1;
+        self.skip_lexical_errors();
+    }
+
+    /// Reports and steps over `Error` tokens. Every lexical error is reported,
+    /// but the parse errors they cause are suppressed until the next statement.
+    fn skip_lexical_errors(&mut self) {
+        while let TokenType::Error(error) = self.current().tokentype {
+            self.report(self.current(), error.into());
+            self.panic_mode = true;
+            self.current_index += 1;
+        }
     }
 
     #[inline]
     fn check(&self, tokentype: TokenType) -> bool {
-        self.tokens.get(self.current_index).unwrap().tokentype == tokentype
+        self.current().tokentype == tokentype
     }
 
     #[inline]
     fn match_tokentype(&mut self, tokentype: TokenType) -> bool {
         let m = self.check(tokentype);
         if m {
-            self.current_index += 1;
+            self.advance();
         }
         m
     }
 
     #[inline]
-    fn consume(&mut self, tokentype: TokenType, msg: String) {
+    fn consume(&mut self, tokentype: TokenType, msg: &str) {
         if !self.match_tokentype(tokentype) {
-            panic!("{:?}", msg)
+            self.error_at_current(msg)
+        }
+    }
+
+    fn error_at(&mut self, token: Token, msg: &str) {
+        // only the first error of a statement is reported, the rest tend to be noise
+        if self.panic_mode {
+            return;
+        }
+        self.panic_mode = true;
+        self.report(token, msg);
+    }
+
+    fn report(&mut self, token: Token, msg: &str) {
+        self.had_error = true;
+
+        let line_start = self.chars[..token.start].iter().rposition(|&c| c == '\n').map_or(0, |i| i + 1);
+        let lexeme = match token.tokentype {
+            TokenType::Newline | TokenType::Dedent | TokenType::Eof => String::new(),
+            _ => self.lexeme(token),
+        };
+        self.errors.push(Diagnostic { message: String::from(msg), line: token.line + 1, column: token.start - line_start + 1, lexeme });
+    }
+
+    #[inline]
+    fn error(&mut self, msg: &str) {
+        self.error_at(self.previous(), msg)
+    }
+
+    #[inline]
+    fn error_at_current(&mut self, msg: &str) {
+        self.error_at(self.current(), msg)
+    }
+
+    /// Skips tokens until something that looks like the start of a statement so
+    /// that parsing can resume after an error.
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+
+        while !self.check(TokenType::Eof) {
+            if self.previous().tokentype == TokenType::Newline {
+                return;
+            }
+            if let TokenType::Dedent | TokenType::Keyword(Keyword::Assert | Keyword::If | Keyword::While | Keyword::For | Keyword::Class | Keyword::Pass | Keyword::Break | Keyword::Continue | Keyword::Def | Keyword::Return | Keyword::Global | Keyword::Nonlocal) = self.current().tokentype {
+                return;
+            }
+            self.advance();
+        }
+    }
+
+    #[inline]
+    fn current_chunk(&mut self) -> &mut Chunk {
+        &mut self.compilers.last_mut().unwrap().function.chunk
+    }
+
+    #[inline]
+    fn emit_byte(&mut self, op: Op, line: usize) {
+        self.current_chunk().write(BytecodeOp::Op(op), line);
+    }
+
+    fn emit_constant(&mut self, value: Value, line: usize) {
+        match self.current_chunk().add_constant(value) {
+            Some(index) => {
+                self.emit_byte(Op::Constant, line);
+                self.current_chunk().write(BytecodeOp::ConstantIndex(index), line);
+            }
+            None => self.error("too many constants in one chunk"),
+        }
+    }
+
+    /// Adds the name of `token` to the constant pool, reusing an existing entry
+    /// for the same name.
+    fn identifier_constant(&mut self, token: Token) -> Option<u8> {
+        let name = intern(self.strings, &self.lexeme(token));
+        let chunk = self.current_chunk();
+        let existing = chunk.constants.iter().position(|constant| {
+            matches!(constant, Value::Object(Object::String(x)) if Rc::ptr_eq(x, &name))
+        });
+
+        match existing {
+            Some(index) => Some(index as u8),
+            None => chunk.add_constant(Value::Object(Object::String(name))),
+        }
+    }
+
+    fn emit_variable(&mut self, op: Op, token: Token) {
+        match self.identifier_constant(token) {
+            Some(index) => {
+                self.emit_byte(op, token.line);
+                self.current_chunk().write(BytecodeOp::ConstantIndex(index), token.line);
+            }
+            None => self.error("too many constants in one chunk"),
+        }
+    }
+
+    /// Emits a jump with a placeholder distance, returning the index of the
+    /// distance for `patch_jump`.
+    fn emit_jump(&mut self, op: Op, line: usize) -> usize {
+        self.emit_byte(op, line);
+        self.current_chunk().write(BytecodeOp::JumpDistance(0), line);
+        self.current_chunk().codes.len() - 1
+    }
+
+    /// Emits a backwards jump to the instruction at `start`.
+    fn emit_loop(&mut self, start: usize, line: usize) {
+        self.emit_byte(Op::Loop, line);
+        // the distance is counted from after itself
+        let distance = self.current_chunk().codes.len() + 1 - start;
+        match u16::try_from(distance) {
+            Ok(distance) => self.current_chunk().write(BytecodeOp::JumpDistance(distance), line),
+            Err(_) => self.error("loop body too large"),
+        }
+    }
+
+    /// Points the jump whose distance is at `index` to the next instruction.
+    fn patch_jump(&mut self, index: usize) {
+        let distance = self.current_chunk().codes.len() - index - 1;
+        match u16::try_from(distance) {
+            Ok(distance) => self.current_chunk().codes[index] = BytecodeOp::JumpDistance(distance),
+            Err(_) => self.error("too much code to jump over"),
+        }
+    }
+
+    /// Applies a unary operator to a lone numeric constant at compile time, so
+    /// negative literals are single constants. Returns whether it folded.
+    fn fold_unary(&mut self, op: Op, operand_start: usize) -> bool {
+        let chunk = self.current_chunk();
+        let index = match chunk.codes[operand_start..] {
+            [BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(index)] => usize::from(index),
+            _ => return false,
+        };
+
+        let constant = chunk.constants[index].clone();
+        if !is_number(&constant) {
+            return false;
+        }
+        let folded = match op {
+            Op::Negative => -constant,
+            _ => constant.positive(),
+        };
+
+        match folded {
+            Ok(value) => {
+                chunk.constants[index] = value;
+                true
+            }
+            Err(_) => false,
         }
     }
 
+    /// Finishes the innermost function with its implicit return. The script
+    /// returns the value its last statement left on the stack.
+    fn end_compiler(&mut self) -> ObjFucntion {
+        let line = self.previous().line;
+        if self.compilers.last().unwrap().functype == FuncType::Script {
+            self.emit_byte(Op::Return, line);
+        } else {
+            self.emit_return(line);
+        }
+        self.compilers.pop().unwrap().function
+    }
+
     pub fn parse_precedence(&mut self, precedence: Precedence) {
-        self.current_index += 1;
+        self.advance();
 
         let assignable = precedence <= Precedence::Assignment;
+        let operand_start = self.current_chunk().codes.len();
 
         let prefix = Into::<ParseRule>::into(self.previous().tokentype).prefix;
         match prefix {
             Some(parsefn) => parsefn(self, assignable),
-            None => panic!("prefix is None")
+            None => return self.error("expected expression")
         }
 
         while precedence <= Into::<ParseRule>::into(self.current().tokentype).precedence {
-            self.current_index += 1;
+            self.advance();
 
             let infix = Into::<ParseRule>::into(self.previous().tokentype).infix;
+            self.operand_start = operand_start;
             match infix {
                 Some(parsefn) => parsefn(self, assignable),
-                None => panic!("infix is None")
+                None => return self.error("expected expression")
             }
         }
 
         if assignable && self.match_tokentype(TokenType::Operator(Operator::Equal)) {
-            panic!("invalid assignment target")
+            self.error("invalid assignment target")
         }
 
     }
@@ -173,34 +428,618 @@ impl<'vm> Parser<'vm> {
     }
 
     fn expression_statement(&mut self) {
-        self.expression()
+        self.expression();
+        // only a statement at the top of the script can be its result, and
+        // setting an attribute has none
+        let codes = &self.compilers.last().unwrap().function.chunk.codes;
+        let sets_attribute = codes.len() >= 2 && codes[codes.len() - 2] == BytecodeOp::Op(Op::SetAttribute);
+        if self.compilers.len() == 1 && self.compilers[0].scope_depth == 0 && !sets_attribute {
+            self.result_pop = Some(self.current_chunk().codes.len());
+        }
+        self.emit_byte(Op::Pop, self.previous().line);
+        self.consume(TokenType::Newline, "expected newline after statement");
     }
 
     fn statement(&mut self) {
         if self.match_tokentype(TokenType::Keyword(Keyword::Assert)) {
             self.assert();
+        } else if self.match_tokentype(TokenType::Keyword(Keyword::If)) {
+            self.if_statement();
+        } else if self.match_tokentype(TokenType::Keyword(Keyword::While)) {
+            self.while_statement();
+        } else if self.match_tokentype(TokenType::Keyword(Keyword::Class)) {
+            self.class_statement();
+        } else if self.match_tokentype(TokenType::Keyword(Keyword::Def)) {
+            self.def_statement();
+        } else if self.match_tokentype(TokenType::Keyword(Keyword::Global)) {
+            self.scope_statement(Keyword::Global);
+        } else if self.match_tokentype(TokenType::Keyword(Keyword::Nonlocal)) {
+            self.scope_statement(Keyword::Nonlocal);
+        } else if self.match_tokentype(TokenType::Keyword(Keyword::Return)) {
+            self.return_statement();
+        } else if self.match_tokentype(TokenType::Keyword(Keyword::For)) {
+            self.for_statement();
+        } else if self.match_tokentype(TokenType::Keyword(Keyword::Break)) {
+            self.break_statement();
+        } else if self.match_tokentype(TokenType::Keyword(Keyword::Continue)) {
+            self.continue_statement();
+        } else if self.match_tokentype(TokenType::Keyword(Keyword::Pass)) {
+            self.consume(TokenType::Newline, "expected newline after statement");
+        } else if self.check(TokenType::Identifier) && self.next().tokentype == TokenType::Operator(Operator::Equal) {
+            self.assignment();
         } else {
             self.expression_statement()
         }
     }
 
     pub fn declaration(&mut self) {
-        self.statement()
+        if self.match_tokentype(TokenType::Indent) {
+            // still parse the stray block so its contents are checked too
+            self.error("unexpected indent");
+            self.panic_mode = false;
+            self.begin_scope();
+            self.block_body();
+            self.end_scope();
+        } else {
+            self.statement();
+        }
+
+        if self.panic_mode {
+            self.synchronize();
+        }
     }
 
-    fn assert(&mut self) {
+    /// Parses the body of a compound statement after its `:`, either a simple
+    /// statement on the same line or an indented block of declarations. Locals
+    /// assigned in the body go out of scope at its end.
+    fn block(&mut self) {
+        self.begin_scope();
+        if !self.match_tokentype(TokenType::Newline) {
+            self.statement();
+        } else if !self.match_tokentype(TokenType::Indent) {
+            self.error_at_current("expected an indented block");
+        } else {
+            self.block_body();
+        }
+        self.end_scope();
+    }
+
+    fn begin_scope(&mut self) {
+        self.compilers.last_mut().unwrap().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let line = self.previous().line;
+        let compiler = self.compilers.last_mut().unwrap();
+        compiler.scope_depth -= 1;
+
+        let depth = compiler.scope_depth;
+        let pops = self.pop_locals(depth, line);
+        let locals = &mut self.compilers.last_mut().unwrap().locals;
+        locals.truncate(locals.len() - pops);
+    }
+
+    /// Emits a Pop, or a CloseUpvalue if captured, for every local deeper than
+    /// `depth`, returning how many, without forgetting them.
+    fn pop_locals(&mut self, depth: usize, line: usize) -> usize {
+        let locals = &self.compilers.last().unwrap().locals;
+        let captured: Vec<bool> = locals.iter().rev()
+            .take_while(|local| local.depth.is_none_or(|d| d > depth))
+            .map(|local| local.captured)
+            .collect();
+        for &captured in captured.iter() {
+            self.emit_byte(if captured { Op::CloseUpvalue } else { Op::Pop }, line);
+        }
+        captured.len()
+    }
+
+    /// Finds the innermost local called `token`, returning its slot and
+    /// whether it has been initialized.
+    fn find_local(&self, token: Token) -> Option<(u8, bool)> {
+        self.find_local_at(self.compilers.len() - 1, token)
+    }
+
+    /// Like `find_local`, in the function compiled by `compiler`.
+    fn find_local_at(&self, compiler: usize, token: Token) -> Option<(u8, bool)> {
+        let name = self.lexeme(token);
+        let locals = &self.compilers[compiler].locals;
+        locals.iter().enumerate().rev()
+            .find(|(_, local)| local.name.length > 0 && self.lexeme(local.name) == name)
+            .map(|(slot, local)| (slot as u8, local.depth.is_some()))
+    }
+
+    fn resolve_local(&mut self, token: Token) -> Option<u8> {
+        let (slot, initialized) = self.find_local(token)?;
+        if !initialized {
+            self.error("cannot read local variable in its own initializer");
+        }
+        Some(slot)
+    }
+
+    /// Scripts keep their variables in globals; functions in stack slots.
+    #[inline]
+    fn uses_locals(&self) -> bool {
+        self.compilers.last().unwrap().functype != FuncType::Script
+    }
+
+    fn add_local(&mut self, name: Token) {
+        let compiler = self.compilers.last_mut().unwrap();
+        if compiler.locals.len() > u8::MAX.into() {
+            self.error("too many local variables in function");
+            return;
+        }
+        compiler.locals.push(Local { name, depth: None, captured: false });
+    }
+
+    fn emit_slot(&mut self, op: Op, slot: u8, line: usize) {
+        self.emit_byte(op, line);
+        self.current_chunk().write(BytecodeOp::SlotIndex(slot), line);
+    }
+
+    fn emit_upvalue(&mut self, op: Op, index: u8, line: usize) {
+        self.emit_byte(op, line);
+        self.current_chunk().write(BytecodeOp::UpvalueIndex(index), line);
+    }
+
+    /// The `global` or `nonlocal` statement the current function made about
+    /// `name`, if any.
+    fn declared(&self, name: Token) -> Option<Declaration> {
+        self.compilers.last().unwrap().declarations.get(&self.lexeme(name)).copied()
+    }
+
+    /// Finds `token` among the variables of the functions enclosing the one
+    /// compiled by `compiler`, capturing it into every function in between.
+    fn resolve_upvalue(&mut self, compiler: usize, token: Token) -> Option<u8> {
+        let enclosing = compiler.checked_sub(1)?;
+        // the script's variables are globals, which are never captured
+        if self.compilers[enclosing].functype == FuncType::Script {
+            return None;
+        }
+
+        match self.compilers[enclosing].declarations.get(&self.lexeme(token)) {
+            Some(Declaration::Global) => return None,
+            Some(&Declaration::Nonlocal(index)) => return self.add_upvalue(compiler, Upvalue { local: false, index }),
+            None => {}
+        }
+
+        if let Some((slot, _)) = self.find_local_at(enclosing, token) {
+            self.compilers[enclosing].locals[usize::from(slot)].captured = true;
+            return self.add_upvalue(compiler, Upvalue { local: true, index: slot });
+        }
+
+        let index = self.resolve_upvalue(enclosing, token)?;
+        self.add_upvalue(compiler, Upvalue { local: false, index })
+    }
+
+    fn add_upvalue(&mut self, compiler: usize, upvalue: Upvalue) -> Option<u8> {
+        let upvalues = &mut self.compilers[compiler].upvalues;
+        if let Some(index) = upvalues.iter().position(|&existing| existing == upvalue) {
+            return Some(index as u8);
+        }
+        // `upvalue_count` is a u8, so the last index is left unused
+        if upvalues.len() >= u8::MAX.into() {
+            self.error("too many closure variables in function");
+            return None;
+        }
+        upvalues.push(upvalue);
+        Some((upvalues.len() - 1) as u8)
+    }
+
+    fn block_body(&mut self) {
+        while !self.check(TokenType::Dedent) && !self.check(TokenType::Eof) {
+            self.declaration();
+        }
+        self.consume(TokenType::Dedent, "expected dedent after block");
+    }
+
+    /// Compiles `a = b = value`, assigning the targets from left to right.
+    fn assignment(&mut self) {
+        let mut names = vec![];
+        while self.check(TokenType::Identifier) && self.next().tokentype == TokenType::Operator(Operator::Equal) {
+            self.advance();
+            names.push(self.previous());
+            self.advance();
+        }
+
+        // new locals are declared before the value so that reading them in it
+        // is caught; each one takes the stack slot its copy of the value lands in
+        let targets: Vec<Target> = names.iter().map(|&name| self.assignment_target(name)).collect();
         self.expression();
-        emit_byte(Op::Assert, self.previous().line);
+
+        let last = targets.len() - 1;
+        for (i, (&name, target)) in names.iter().zip(targets).enumerate() {
+            self.store(target, name, i == last);
+        }
+        self.consume(TokenType::Newline, "expected newline after statement");
     }
 
-}
+    /// Decides where assigning to `name` stores, declaring it as a new local
+    /// if need be.
+    fn assignment_target(&mut self, name: Token) -> Target {
+        if !self.uses_locals() {
+            return Target::Global;
+        }
+        match self.declared(name) {
+            Some(Declaration::Global) => Target::Global,
+            Some(Declaration::Nonlocal(index)) => Target::Upvalue(index),
+            None => match self.find_local(name) {
+                Some((slot, _)) => Target::Local(slot),
+                None => {
+                    self.add_local(name);
+                    Target::NewLocal((self.compilers.last().unwrap().locals.len() - 1) as u8)
+                }
+            },
+        }
+    }
+
+    /// Stores the value on top of the stack in `target`. The `last` store of a
+    /// statement consumes the value, the others leave it for the next.
+    fn store(&mut self, target: Target, name: Token, last: bool) {
+        let line = name.line;
+        match target {
+            Target::Global if last => self.emit_variable(Op::DefineGlobal, name),
+            Target::Global => self.emit_variable(Op::SetGlobal, name),
+            Target::NewLocal(slot) => {
+                self.mark_initialized(slot);
+                if !last {
+                    self.emit_slot(Op::GetLocal, slot, line);
+                }
+            }
+            Target::Local(slot) => {
+                self.emit_slot(Op::SetLocal, slot, line);
+                if last {
+                    self.emit_byte(Op::Pop, line);
+                }
+            }
+            Target::Upvalue(index) => {
+                self.emit_upvalue(Op::SetUpvalue, index, line);
+                if last {
+                    self.emit_byte(Op::Pop, line);
+                }
+            }
+        }
+    }
+
+    fn mark_initialized(&mut self, slot: u8) {
+        let compiler = self.compilers.last_mut().unwrap();
+        compiler.locals[usize::from(slot)].depth = Some(compiler.scope_depth);
+    }
+
+    /// Stores the value on top of the stack in the variable `name`. A new
+    /// local keeps the value's stack slot.
+    fn define_variable(&mut self, name: Token) {
+        let target = self.assignment_target(name);
+        self.store(target, name, true);
+    }
+
+    /// Compiles `def name(params):` and its body into a function assigned to
+    /// `name`.
+    fn def_statement(&mut self) {
+        self.consume(TokenType::Identifier, "expected function name after 'def'");
+        let name = self.previous();
+        // a local function can call itself, through the slot its closure lands in
+        let target = self.assignment_target(name);
+        if let Target::NewLocal(slot) = target {
+            self.mark_initialized(slot);
+        }
+        self.function(FuncType::Function, name);
+        self.store(target, name, true);
+    }
+
+    /// Compiles the parameters and body of a function called `name`, leaving
+    /// a closure over it on the stack.
+    fn function(&mut self, functype: FuncType, name: Token) {
+        self.compilers.push(Compiler::new(functype, self.lexeme(name)));
+        self.begin_scope();
+
+        self.consume(TokenType::Operator(Operator::LeftParen), "expected '(' after function name");
+        while !self.check(TokenType::Operator(Operator::RightParen)) {
+            self.consume(TokenType::Identifier, "expected parameter name");
+            let parameter = self.previous();
+            let function = &mut self.compilers.last_mut().unwrap().function;
+            if function.arity == u8::MAX {
+                self.error("cannot have more than 255 parameters");
+            } else {
+                function.arity += 1;
+            }
+
+            if self.find_local(parameter).is_some() {
+                self.error(&format!("duplicate argument '{}' in function definition", self.lexeme(parameter)));
+            } else {
+                self.define_variable(parameter);
+            }
+
+            if !self.match_tokentype(TokenType::Operator(Operator::Comma)) {
+                break;
+            }
+        }
+        self.consume(TokenType::Operator(Operator::RightParen), "expected ')' after parameters");
+        self.consume(TokenType::Operator(Operator::Colon), "expected ':' after parameters");
+        self.block();
+
+        let upvalues = std::mem::take(&mut self.compilers.last_mut().unwrap().upvalues);
+        let mut function = self.end_compiler();
+        function.upvalue_count = upvalues.len() as u8;
+
+        match self.current_chunk().add_constant(Value::Object(Object::Function(Rc::new(function)))) {
+            Some(index) => {
+                self.emit_byte(Op::Closure, name.line);
+                self.current_chunk().write(BytecodeOp::ConstantIndex(index), name.line);
+                for Upvalue { local, index } in upvalues {
+                    self.current_chunk().write(BytecodeOp::Capture { local, index }, name.line);
+                }
+            }
+            None => self.error("too many constants in one chunk"),
+        }
+    }
+
+    /// Returns `None`, or the instance being initialized from `__init__`.
+    fn emit_return(&mut self, line: usize) {
+        if self.compilers.last().unwrap().functype == FuncType::Initializer {
+            self.emit_slot(Op::GetLocal, 0, line);
+        } else {
+            self.emit_byte(Op::None, line);
+        }
+        self.emit_byte(Op::Return, line);
+    }
+
+    /// Compiles `class Name:` or `class Name(Superclass):` and its body of
+    /// methods and attributes into a class assigned to `Name`.
+    fn class_statement(&mut self) {
+        self.consume(TokenType::Identifier, "expected class name after 'class'");
+        let name = self.previous();
+        let target = self.assignment_target(name);
+        // `class Name():` has no superclass either
+        if self.match_tokentype(TokenType::Operator(Operator::LeftParen)) && !self.match_tokentype(TokenType::Operator(Operator::RightParen)) {
+            self.expression();
+            self.consume(TokenType::Operator(Operator::RightParen), "expected ')' after superclass");
+            self.emit_variable(Op::Subclass, name);
+        } else {
+            self.emit_variable(Op::Class, name);
+        }
+        // methods of a local class can name it once it is in its slot
+        if let Target::NewLocal(slot) = target {
+            self.mark_initialized(slot);
+        }
+        self.consume(TokenType::Operator(Operator::Colon), "expected ':' after class name");
+
+        // the class stays on the stack while its body adds to it
+        if !self.match_tokentype(TokenType::Newline) {
+            self.class_member();
+        } else if !self.match_tokentype(TokenType::Indent) {
+            self.error_at_current("expected an indented block");
+        } else {
+            while !self.check(TokenType::Dedent) && !self.check(TokenType::Eof) {
+                self.class_member();
+                if self.panic_mode {
+                    self.synchronize();
+                }
+            }
+            self.consume(TokenType::Dedent, "expected dedent after block");
+        }
+        self.store(target, name, true);
+    }
+
+    fn class_member(&mut self) {
+        if self.match_tokentype(TokenType::Keyword(Keyword::Def)) {
+            self.consume(TokenType::Identifier, "expected method name after 'def'");
+            let name = self.previous();
+            let functype = if self.lexeme(name) == "__init__" { FuncType::Initializer } else { FuncType::Method };
+            self.function(functype, name);
+            self.emit_variable(Op::Method, name);
+        } else if self.match_tokentype(TokenType::Keyword(Keyword::Pass)) {
+            self.consume(TokenType::Newline, "expected newline after statement");
+        } else if self.check(TokenType::Identifier) && self.next().tokentype == TokenType::Operator(Operator::Equal) {
+            self.advance();
+            let name = self.previous();
+            self.advance();
+            self.expression();
+            self.emit_variable(Op::Method, name);
+            self.consume(TokenType::Newline, "expected newline after statement");
+        } else {
+            self.error_at_current("expected a method or attribute in class body");
+        }
+    }
+
+    /// Compiles `global names` or `nonlocal names`, which make assignments to
+    /// the names in this function rebind them outside it.
+    fn scope_statement(&mut self, keyword: Keyword) {
+        let kind = if keyword == Keyword::Global { "global" } else { "nonlocal" };
+        loop {
+            self.consume(TokenType::Identifier, &format!("expected name after '{}'", kind));
+            let name = self.previous();
+            let lexeme = self.lexeme(name);
+
+            if !self.uses_locals() {
+                if keyword == Keyword::Nonlocal {
+                    self.error("nonlocal declaration not allowed at module level");
+                }
+            } else if self.find_local(name).is_some() {
+                self.error(&format!("name '{}' is assigned to before {} declaration", lexeme, kind));
+            } else {
+                let declaration = match keyword {
+                    Keyword::Global => Some(Declaration::Global),
+                    _ => self.resolve_upvalue(self.compilers.len() - 1, name).map(Declaration::Nonlocal),
+                };
+                match declaration {
+                    Some(declaration) => {
+                        let previous = self.compilers.last_mut().unwrap().declarations.insert(lexeme.clone(), declaration);
+                        if previous.is_some_and(|previous| (previous == Declaration::Global) != (declaration == Declaration::Global)) {
+                            self.error(&format!("name '{}' is nonlocal and global", lexeme));
+                        }
+                    }
+                    None => self.error(&format!("no binding for nonlocal '{}' found", lexeme)),
+                }
+            }
+
+            if !self.match_tokentype(TokenType::Operator(Operator::Comma)) {
+                break;
+            }
+        }
+        self.consume(TokenType::Newline, "expected newline after statement");
+    }
+
+    fn return_statement(&mut self) {
+        let line = self.previous().line;
+        if !self.uses_locals() {
+            self.error("'return' outside function");
+        }
+
+        if self.check(TokenType::Newline) {
+            self.emit_return(line);
+        } else {
+            if self.compilers.last().unwrap().functype == FuncType::Initializer {
+                self.error("cannot return a value from __init__");
+            }
+            self.expression();
+            self.emit_byte(Op::Return, line);
+        }
+        self.consume(TokenType::Newline, "expected newline after statement");
+    }
+
+    /// Compiles the rest of an `if` or `elif` after its keyword, including any
+    /// `elif` and `else` clauses that follow.
+    fn if_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::Operator(Operator::Colon), "expected ':' after condition");
+
+        let then_jump = self.emit_jump(Op::JumpIfFalse, self.previous().line);
+        self.block();
+
+        if !self.check(TokenType::Keyword(Keyword::Elif)) && !self.check(TokenType::Keyword(Keyword::Else)) {
+            self.patch_jump(then_jump);
+            return;
+        }
+
+        let else_jump = self.emit_jump(Op::Jump, self.previous().line);
+        self.patch_jump(then_jump);
+
+        if self.match_tokentype(TokenType::Keyword(Keyword::Elif)) {
+            self.if_statement();
+        } else {
+            self.advance();
+            self.consume(TokenType::Operator(Operator::Colon), "expected ':' after 'else'");
+            self.block();
+        }
+        self.patch_jump(else_jump);
+    }
+
+    /// Compiles a `while` loop and its optional `else` clause, which runs when
+    /// the condition turns false but is skipped by `break`.
+    fn while_statement(&mut self) {
+        let line = self.previous().line;
+        let start = self.current_chunk().codes.len();
+        self.expression();
+        self.consume(TokenType::Operator(Operator::Colon), "expected ':' after condition");
+
+        let exit_jump = self.emit_jump(Op::JumpIfFalse, line);
+        let depth = self.compilers.last().unwrap().scope_depth;
+        self.compilers.last_mut().unwrap().loops.push(Loop { start, depth, breaks: vec![] });
+        self.block();
+        self.emit_loop(start, line);
+        let breaks = self.compilers.last_mut().unwrap().loops.pop().unwrap().breaks;
+
+        self.patch_jump(exit_jump);
+        if self.match_tokentype(TokenType::Keyword(Keyword::Else)) {
+            self.consume(TokenType::Operator(Operator::Colon), "expected ':' after 'else'");
+            self.block();
+        }
+        for index in breaks {
+            self.patch_jump(index);
+        }
+    }
+
+    /// Compiles `for name in iterable:` with an optional `else` clause. The
+    /// iterator is kept in a hidden local for the length of the loop.
+    fn for_statement(&mut self) {
+        let line = self.previous().line;
+        self.consume(TokenType::Identifier, "expected loop variable after 'for'");
+        let target = self.previous();
+        self.consume(TokenType::Keyword(Keyword::In), "expected 'in' after loop variable");
+        self.expression();
+        self.consume(TokenType::Operator(Operator::Colon), "expected ':' after iterable");
+
+        self.begin_scope();
+        self.emit_byte(Op::GetIter, line);
+        let depth = self.compilers.last().unwrap().scope_depth;
+        self.add_local(Token { tokentype: TokenType::Identifier, start: 0, length: 0, line });
+        self.compilers.last_mut().unwrap().locals.last_mut().unwrap().depth = Some(depth);
+
+        let start = self.current_chunk().codes.len();
+        let exit_jump = self.emit_jump(Op::ForIter, line);
+
+        // a new loop variable lives in the slot the next value is pushed to
+        self.begin_scope();
+        self.define_variable(target);
+
+        self.compilers.last_mut().unwrap().loops.push(Loop { start, depth, breaks: vec![] });
+        self.block();
+        self.end_scope();
+        self.emit_loop(start, line);
+        let breaks = self.compilers.last_mut().unwrap().loops.pop().unwrap().breaks;
+
+        self.patch_jump(exit_jump);
+        if self.match_tokentype(TokenType::Keyword(Keyword::Else)) {
+            self.consume(TokenType::Operator(Operator::Colon), "expected ':' after 'else'");
+            self.block();
+        }
+        for index in breaks {
+            self.patch_jump(index);
+        }
+        self.end_scope();
+    }
+
+    fn break_statement(&mut self) {
+        let line = self.previous().line;
+        match self.compilers.last().unwrap().loops.last() {
+            Some(innermost) => {
+                let depth = innermost.depth;
+                self.pop_locals(depth, line);
+                let index = self.emit_jump(Op::Jump, line);
+                self.compilers.last_mut().unwrap().loops.last_mut().unwrap().breaks.push(index);
+            }
+            None => self.error("'break' outside loop"),
+        }
+        self.consume(TokenType::Newline, "expected newline after statement");
+    }
+
+    fn continue_statement(&mut self) {
+        let line = self.previous().line;
+        match self.compilers.last().unwrap().loops.last() {
+            Some(innermost) => {
+                let (start, depth) = (innermost.start, innermost.depth);
+                self.pop_locals(depth, line);
+                self.emit_loop(start, line);
+            }
+            None => self.error("'continue' not properly in loop"),
+        }
+        self.consume(TokenType::Newline, "expected newline after statement");
+    }
+
+    fn assert(&mut self) {
+        self.expression();
+        self.emit_byte(Op::Assert, self.previous().line);
+        self.consume(TokenType::Newline, "expected newline after statement");
+    }
 
-fn emit_byte(op: Op, line: usize) {
-    todo!("")
 }
 
 fn unary(parser: &mut Parser, _: bool) {
-    todo!("")
+    let previous = parser.previous();
+    let operand_start = parser.current_chunk().codes.len();
+
+    // binds looser than `**`, so `-2**2` is `-(2**2)`
+    parser.parse_precedence(Precedence::Unary);
+
+    let op = match previous.tokentype {
+        TokenType::Operator(Operator::Minus) => Op::Negative,
+        TokenType::Operator(Operator::Plus) => Op::Positive,
+        _ => panic!("unknown unary operator"),
+    };
+
+    if !parser.fold_unary(op, operand_start) {
+        parser.emit_byte(op, previous.line);
+    }
 }
 
 fn binary(parser: &mut Parser, _: bool) {
@@ -209,43 +1048,511 @@ fn binary(parser: &mut Parser, _: bool) {
     let line = previous.line;
     let parse_rule: ParseRule = tokentype.into();
 
-    parser.parse_precedence(parse_rule.precedence.next());
+    // `**` is right-associative
+    if tokentype == TokenType::Operator(Operator::StarStar) {
+        parser.parse_precedence(Precedence::Exponent);
+    } else {
+        parser.parse_precedence(parse_rule.precedence.next());
+    }
 
     match tokentype {
-        TokenType::Operator(Operator::Plus) => emit_byte(Op::Add, line),
-        TokenType::Operator(Operator::Minus) => emit_byte(Op::Subtract, line),
-        TokenType::Operator(Operator::Star) => emit_byte(Op::Multiply, line),
-        TokenType::Operator(Operator::Slash) => emit_byte(Op::Divide, line),
-        TokenType::Operator(Operator::StarStar) => emit_byte(Op::Exponent, line),
-        TokenType::Operator(Operator::SlashSlash) => emit_byte(Op::IntDivide, line),
-        TokenType::Operator(Operator::Less) => emit_byte(Op::Less, line),
-        TokenType::Operator(Operator::LessEqual) => emit_byte(Op::LessEqual, line),
-        TokenType::Operator(Operator::Greater) => emit_byte(Op::Greater, line),
-        TokenType::Operator(Operator::GreaterEqual) => emit_byte(Op::Greater, line),
-        TokenType::Operator(Operator::EqualEqual) => emit_byte(Op::ValueEqual, line),
-        TokenType::Operator(Operator::NotEqual) => emit_byte(Op::NotValueEqual, line),
+        TokenType::Operator(Operator::Plus) => parser.emit_byte(Op::Add, line),
+        TokenType::Operator(Operator::Minus) => parser.emit_byte(Op::Subtract, line),
+        TokenType::Operator(Operator::Star) => parser.emit_byte(Op::Multiply, line),
+        TokenType::Operator(Operator::Slash) => parser.emit_byte(Op::Divide, line),
+        TokenType::Operator(Operator::StarStar) => parser.emit_byte(Op::Exponent, line),
+        TokenType::Operator(Operator::SlashSlash) => parser.emit_byte(Op::IntDivide, line),
         _ => panic!("unknown binary operator"),
     }
 
 
 }
 
+fn not(parser: &mut Parser, _: bool) {
+    let line = parser.previous().line;
+    parser.parse_precedence(Precedence::Not);
+    parser.emit_byte(Op::Not, line);
+}
+
+/// `a and b` leaves `a` if it is falsy, otherwise `b`.
+fn and(parser: &mut Parser, _: bool) {
+    let end_jump = parser.emit_jump(Op::JumpIfFalseOrPop, parser.previous().line);
+    parser.parse_precedence(Precedence::And.next());
+    parser.patch_jump(end_jump);
+}
+
+/// `a or b` leaves `a` if it is truthy, otherwise `b`.
+fn or(parser: &mut Parser, _: bool) {
+    let end_jump = parser.emit_jump(Op::JumpIfTrueOrPop, parser.previous().line);
+    parser.parse_precedence(Precedence::Or.next());
+    parser.patch_jump(end_jump);
+}
+
+/// Compiles `a if condition else b`. The code for `a` was emitted before the
+/// `if` was seen, so it is moved to after the condition to only run when
+/// chosen.
+fn conditional(parser: &mut Parser, _: bool) {
+    let line = parser.previous().line;
+    let operand_start = parser.operand_start;
+    let chunk = parser.current_chunk();
+    let operand_codes = chunk.codes.split_off(operand_start);
+    let operand_lines = chunk.lines.split_off(operand_start);
+
+    parser.parse_precedence(Precedence::Conditional.next());
+    let else_jump = parser.emit_jump(Op::JumpIfFalse, line);
+
+    let chunk = parser.current_chunk();
+    chunk.codes.extend(operand_codes);
+    chunk.lines.extend(operand_lines);
+    let end_jump = parser.emit_jump(Op::Jump, line);
+
+    parser.patch_jump(else_jump);
+    parser.consume(TokenType::Keyword(Keyword::Else), "expected 'else' after condition");
+    parser.parse_precedence(Precedence::Conditional);
+    parser.patch_jump(end_jump);
+}
+
+fn comparison_op(tokentype: TokenType) -> Option<&'static [Op]> {
+    match tokentype {
+        TokenType::Operator(Operator::Less) => Some(&[Op::Less]),
+        TokenType::Operator(Operator::LessEqual) => Some(&[Op::LessEqual]),
+        TokenType::Operator(Operator::Greater) => Some(&[Op::Greater]),
+        TokenType::Operator(Operator::GreaterEqual) => Some(&[Op::GreaterEqual]),
+        TokenType::Operator(Operator::EqualEqual) => Some(&[Op::ValueEqual]),
+        TokenType::Operator(Operator::NotEqual) => Some(&[Op::NotValueEqual]),
+        TokenType::Keyword(Keyword::In) => Some(&[Op::Contains]),
+        TokenType::Keyword(Keyword::Not) => Some(&[Op::Contains, Op::Not]),
+        _ => None,
+    }
+}
+
+/// Compiles a whole chain like `a < b <= c` as `a < b and b <= c`, evaluating
+/// each middle operand once and stopping at the first false comparison.
+fn comparison(parser: &mut Parser, _: bool) {
+    let mut operator = parser.previous();
+    let mut false_jumps = vec![];
+
+    loop {
+        if operator.tokentype == TokenType::Keyword(Keyword::Not) {
+            parser.consume(TokenType::Keyword(Keyword::In), "expected 'in' after 'not'");
+        }
+        parser.parse_precedence(Precedence::Comparison.next());
+        let ops = comparison_op(operator.tokentype).expect("unknown comparison operator");
+
+        if comparison_op(parser.current().tokentype).is_none() {
+            for &op in ops {
+                parser.emit_byte(op, operator.line);
+            }
+            break;
+        }
+
+        // keep a copy of the middle operand under the result for the next link
+        parser.emit_byte(Op::Dup, operator.line);
+        parser.emit_byte(Op::Rotate, operator.line);
+        for &op in ops {
+            parser.emit_byte(op, operator.line);
+        }
+        false_jumps.push(parser.emit_jump(Op::JumpIfFalseOrPop, operator.line));
+
+        parser.advance();
+        operator = parser.previous();
+    }
+
+    if false_jumps.is_empty() {
+        return;
+    }
+
+    let line = operator.line;
+    let end_jump = parser.emit_jump(Op::Jump, line);
+    for jump in false_jumps {
+        parser.patch_jump(jump);
+    }
+    // a link was false: drop the leftover middle operand from under the result
+    parser.emit_byte(Op::Swap, line);
+    parser.emit_byte(Op::Pop, line);
+    parser.patch_jump(end_jump);
+}
+
+fn list(parser: &mut Parser, _: bool) {
+    let line = parser.previous().line;
+    let mut count: usize = 0;
+
+    while !parser.check(TokenType::Operator(Operator::RightBracket)) {
+        parser.expression();
+        if count == u8::MAX.into() {
+            parser.error("cannot have more than 255 elements in a list display");
+        }
+        count += 1;
+
+        if !parser.match_tokentype(TokenType::Operator(Operator::Comma)) {
+            break;
+        }
+    }
+    parser.consume(TokenType::Operator(Operator::RightBracket), "expected ']' after list elements");
+
+    parser.emit_byte(Op::BuildList, line);
+    parser.current_chunk().write(BytecodeOp::ElementCount(count as u8), line);
+}
+
 fn grouping(parser: &mut Parser, _: bool) {
-    todo!("")
+    parser.expression();
+    parser.consume(TokenType::Operator(Operator::RightParen), "expected ')' after expression");
 }
 
 fn call(parser: &mut Parser, _: bool) {
-    todo!("")
+    let line = parser.previous().line;
+    let mut num_args: usize = 0;
+
+    while !parser.check(TokenType::Operator(Operator::RightParen)) {
+        parser.expression();
+        if num_args == u8::MAX.into() {
+            parser.error("cannot have more than 255 arguments");
+        }
+        num_args += 1;
+
+        if !parser.match_tokentype(TokenType::Operator(Operator::Comma)) {
+            break;
+        }
+    }
+    parser.consume(TokenType::Operator(Operator::RightParen), "expected ')' after arguments");
+
+    parser.emit_byte(Op::Call, line);
+    parser.current_chunk().write(BytecodeOp::ArgumentCount(num_args.min(u8::MAX.into()) as u8), line);
 }
 
-fn integer(parser: &mut Parser, _: bool) {
+fn dot(parser: &mut Parser, assignable: bool) {
+    parser.consume(TokenType::Identifier, "expected attribute name after '.'");
+    let name = parser.previous();
+    if assignable && parser.match_tokentype(TokenType::Operator(Operator::Equal)) {
+        parser.expression();
+        parser.emit_variable(Op::SetAttribute, name);
+    } else {
+        parser.emit_variable(Op::GetAttribute, name);
+    }
+}
+
+/// Compiles `super().name`: the method's first argument, then the attribute
+/// looked up from the superclass of the method's class.
+fn super_attribute(parser: &mut Parser) {
+    let line = parser.previous().line;
+    parser.advance();
+    parser.consume(TokenType::Operator(Operator::RightParen), "expected ')' after 'super('");
+    parser.consume(TokenType::Operator(Operator::Dot), "expected '.' after 'super()'");
+    parser.consume(TokenType::Identifier, "expected superclass method name");
+    let name = parser.previous();
+
+    let compiler = parser.compilers.last().unwrap();
+    let in_method = matches!(compiler.functype, FuncType::Method | FuncType::Initializer);
+    if !in_method || compiler.function.arity == 0 {
+        parser.error("super() must be called directly in a method with a 'self' parameter");
+    }
+    parser.emit_slot(Op::GetLocal, 1, line);
+    parser.emit_variable(Op::GetSuper, name);
+}
+
+fn variable(parser: &mut Parser, _: bool) {
+    let previous = parser.previous();
+    if parser.lexeme(previous) == "super" && parser.check(TokenType::Operator(Operator::LeftParen)) {
+        return super_attribute(parser);
+    }
+    if parser.declared(previous) == Some(Declaration::Global) {
+        return parser.emit_variable(Op::GetGlobal, previous);
+    }
+
+    if let Some(slot) = parser.resolve_local(previous) {
+        parser.emit_slot(Op::GetLocal, slot, previous.line);
+    } else if let Some(index) = parser.resolve_upvalue(parser.compilers.len() - 1, previous) {
+        parser.emit_upvalue(Op::GetUpvalue, index, previous.line);
+    } else {
+        parser.emit_variable(Op::GetGlobal, previous);
+    }
+}
+
+fn string(parser: &mut Parser, _: bool) {
+    let previous = parser.previous();
+    let lexeme: Vec<char> = parser.lexeme(previous).chars().collect();
+    let quotes = if lexeme.len() >= 6 && lexeme[1] == lexeme[0] && lexeme[2] == lexeme[0] { 3 } else { 1 };
+
+    match unescape(&lexeme[quotes..lexeme.len() - quotes]) {
+        Some(text) => {
+            let string = intern(parser.strings, &text);
+            parser.emit_constant(Value::Object(Object::String(string)), previous.line);
+        }
+        None => parser.error("invalid escape sequence"),
+    }
+}
+
+/// Resolves the backslash escapes of a string literal's body. Unknown escapes
+/// are kept as written, as Python does; `None` means a malformed `\x`, `\u`
+/// or `\U` escape.
+fn unescape(body: &[char]) -> Option<String> {
+    let mut text = String::with_capacity(body.len());
+    let mut chars = body.iter().copied();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+
+        let escaped = match chars.next() {
+            Some('\n') => continue,
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('a') => '\x07',
+            Some('b') => '\x08',
+            Some('f') => '\x0c',
+            Some('v') => '\x0b',
+            Some(c @ ('\\' | '\'' | '"')) => c,
+            Some(c @ ('x' | 'u' | 'U')) => {
+                let digits = match c { 'x' => 2, 'u' => 4, _ => 8 };
+                let hex: String = chars.by_ref().take(digits).collect();
+                if hex.len() != digits {
+                    return None;
+                }
+                char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+            }
+            Some(c) => {
+                text.push('\\');
+                c
+            }
+            None => '\\',
+        };
+        text.push(escaped);
+    }
+
+    Some(text)
+}
+
+fn literal(parser: &mut Parser, _: bool) {
+    let previous = parser.previous();
+    match previous.tokentype {
+        TokenType::Keyword(Keyword::True) => parser.emit_byte(Op::True, previous.line),
+        TokenType::Keyword(Keyword::False) => parser.emit_byte(Op::False, previous.line),
+        TokenType::Keyword(Keyword::None) => parser.emit_byte(Op::None, previous.line),
+        _ => panic!("unknown literal"),
+    }
+}
+
+/// Strips digit-grouping underscores so the lexeme can be handed to `str::parse`.
+#[inline]
+fn number_text(parser: &Parser, token: Token) -> String {
+    parser.lexeme(token).chars().filter(|&c| c != '_').collect()
+}
 
+fn integer(parser: &mut Parser, _: bool) {
+    let previous = parser.previous();
+    match number_text(parser, previous).parse::<BigInt>() {
+        Ok(x) => parser.emit_constant(Value::from(x), previous.line),
+        Err(_) => parser.error("invalid integer literal"),
+    }
 }
 
 fn float(parser: &mut Parser, _: bool) {
-    todo!("")
+    let previous = parser.previous();
+    match number_text(parser, previous).parse::<f64>() {
+        Ok(x) => parser.emit_constant(Value::Float(x), previous.line),
+        Err(_) => parser.error("invalid float literal"),
+    }
 }
 
-fn complex(_parser: &mut Parser, _: bool) {
-    todo!("")
+fn complex(parser: &mut Parser, _: bool) {
+    let previous = parser.previous();
+    let text = number_text(parser, previous);
+    match text[..text.len() - 1].parse::<f64>() {
+        Ok(x) => parser.emit_constant(Value::Complex(Complex::new(0.0, x)), previous.line),
+        Err(_) => parser.error("invalid complex literal"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use hashbrown::HashSet;
+
+    use crate::{token::{Tokenizer, TokenType, Operator}, chunk::{BytecodeOp, Op}, compiler::{Compiler, FuncType}};
+    use super::{Parser, unescape};
+
+    fn parser<'a>(source: &str, strings: &'a mut HashSet<Rc<str>>) -> Parser<'a> {
+        Parser::new(source, Tokenizer::new(source).tokenize(), strings)
+    }
+
+    #[test]
+    fn test_block() {
+        let mut strings = HashSet::new();
+        let mut parser = parser(": 1\n:\n    2\n    assert 3\n4\n", &mut strings);
+        parser.consume(TokenType::Operator(Operator::Colon), "");
+        parser.block();
+        parser.consume(TokenType::Operator(Operator::Colon), "");
+        parser.block();
+        assert!(!parser.had_error);
+        assert!(parser.check(TokenType::Number(crate::token::Number::Integer)));
+
+        let asserts = parser.current_chunk().codes.iter().filter(|&&c| c == BytecodeOp::Op(Op::Assert)).count();
+        assert_eq!(asserts, 1);
+    }
+
+    #[test]
+    fn test_block_requires_indent() {
+        let mut strings = HashSet::new();
+        let mut parser = parser(":\n2\n", &mut strings);
+        parser.consume(TokenType::Operator(Operator::Colon), "");
+        parser.block();
+        assert!(parser.had_error);
+        assert_eq!(parser.errors[0].message, "expected an indented block");
+    }
+
+    /// A parser that compiles `source` as the body of a function rather than
+    /// a script.
+    fn function_parser<'a>(source: &str, strings: &'a mut HashSet<Rc<str>>) -> Parser<'a> {
+        let mut parser = parser(source, strings);
+        parser.compilers.push(Compiler::new(FuncType::Function, String::from("f")));
+        parser
+    }
+
+    fn function_codes(parser: &mut Parser) -> Vec<BytecodeOp> {
+        while !parser.match_tokentype(TokenType::Eof) {
+            parser.declaration();
+        }
+        parser.end_compiler().chunk.codes
+    }
+
+    #[test]
+    fn test_locals() {
+        let mut strings = HashSet::new();
+        let mut parser = function_parser("x = 1\ny = x\nx = y\na = x = b = 2\n", &mut strings);
+        let truth = vec![
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(0),
+            BytecodeOp::Op(Op::GetLocal), BytecodeOp::SlotIndex(1),
+            BytecodeOp::Op(Op::GetLocal), BytecodeOp::SlotIndex(2),
+            BytecodeOp::Op(Op::SetLocal), BytecodeOp::SlotIndex(1),
+            BytecodeOp::Op(Op::Pop),
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(1),
+            BytecodeOp::Op(Op::GetLocal), BytecodeOp::SlotIndex(3),
+            BytecodeOp::Op(Op::SetLocal), BytecodeOp::SlotIndex(1),
+            BytecodeOp::Op(Op::None),
+            BytecodeOp::Op(Op::Return),
+        ];
+        assert_eq!(function_codes(&mut parser), truth);
+        assert!(!parser.had_error);
+    }
+
+    #[test]
+    fn test_locals_are_block_scoped() {
+        let mut strings = HashSet::new();
+        let mut parser = function_parser("x = 1\n:\n    y = x\n    x = y\ny\n", &mut strings);
+        parser.declaration();
+        parser.consume(TokenType::Operator(Operator::Colon), "");
+        parser.block();
+        let codes = function_codes(&mut parser);
+
+        let truth = [
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(0),
+            BytecodeOp::Op(Op::GetLocal), BytecodeOp::SlotIndex(1),
+            BytecodeOp::Op(Op::GetLocal), BytecodeOp::SlotIndex(2),
+            BytecodeOp::Op(Op::SetLocal), BytecodeOp::SlotIndex(1),
+            BytecodeOp::Op(Op::Pop),
+            // `y` goes out of scope with the block, so the last line reads a global
+            BytecodeOp::Op(Op::Pop),
+            BytecodeOp::Op(Op::GetGlobal), BytecodeOp::ConstantIndex(1),
+            BytecodeOp::Op(Op::Pop),
+        ];
+        assert_eq!(codes[..truth.len()], truth);
+    }
+
+    #[test]
+    fn test_loops_pop_locals() {
+        let mut strings = HashSet::new();
+        let mut parser = function_parser("while 1:\n    x = 2\n    if x:\n        y = 3\n        break\n    continue\n", &mut strings);
+        let truth = vec![
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(0),
+            BytecodeOp::Op(Op::JumpIfFalse), BytecodeOp::JumpDistance(19),
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(1),
+            BytecodeOp::Op(Op::GetLocal), BytecodeOp::SlotIndex(1),
+            BytecodeOp::Op(Op::JumpIfFalse), BytecodeOp::JumpDistance(7),
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(2),
+            // `break` pops both `y` and `x`, the end of the `if` block only `y`
+            BytecodeOp::Op(Op::Pop), BytecodeOp::Op(Op::Pop),
+            BytecodeOp::Op(Op::Jump), BytecodeOp::JumpDistance(7),
+            BytecodeOp::Op(Op::Pop),
+            // `continue` pops `x` before jumping back
+            BytecodeOp::Op(Op::Pop),
+            BytecodeOp::Op(Op::Loop), BytecodeOp::JumpDistance(20),
+            BytecodeOp::Op(Op::Pop),
+            BytecodeOp::Op(Op::Loop), BytecodeOp::JumpDistance(23),
+            BytecodeOp::Op(Op::None),
+            BytecodeOp::Op(Op::Return),
+        ];
+        assert_eq!(function_codes(&mut parser), truth);
+        assert!(!parser.had_error);
+    }
+
+    #[test]
+    fn test_for_loop_slots() {
+        let mut strings = HashSet::new();
+        let mut parser = function_parser("for x in y:\n    z = x\n    break\n", &mut strings);
+        let truth = vec![
+            BytecodeOp::Op(Op::GetGlobal), BytecodeOp::ConstantIndex(0),
+            // the iterator takes slot 1 and each `x` slot 2
+            BytecodeOp::Op(Op::GetIter),
+            BytecodeOp::Op(Op::ForIter), BytecodeOp::JumpDistance(10),
+            BytecodeOp::Op(Op::GetLocal), BytecodeOp::SlotIndex(2),
+            // `break` pops `z` and `x` but leaves the iterator to the end of the loop
+            BytecodeOp::Op(Op::Pop), BytecodeOp::Op(Op::Pop),
+            BytecodeOp::Op(Op::Jump), BytecodeOp::JumpDistance(4),
+            BytecodeOp::Op(Op::Pop),
+            BytecodeOp::Op(Op::Pop),
+            BytecodeOp::Op(Op::Loop), BytecodeOp::JumpDistance(12),
+            BytecodeOp::Op(Op::Pop),
+            BytecodeOp::Op(Op::None),
+            BytecodeOp::Op(Op::Return),
+        ];
+        assert_eq!(function_codes(&mut parser), truth);
+        assert!(!parser.had_error);
+    }
+
+    #[test]
+    fn test_captured_locals_are_closed() {
+        let mut strings = HashSet::new();
+        let mut parser = function_parser("while 1:\n    x = 1\n    def g(): return x\n", &mut strings);
+        let truth = vec![
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(0),
+            BytecodeOp::Op(Op::JumpIfFalse), BytecodeOp::JumpDistance(9),
+            BytecodeOp::Op(Op::Constant), BytecodeOp::ConstantIndex(1),
+            BytecodeOp::Op(Op::Closure), BytecodeOp::ConstantIndex(2), BytecodeOp::Capture { local: true, index: 1 },
+            // `g` goes out of scope before `x`, which `g` captured
+            BytecodeOp::Op(Op::Pop),
+            BytecodeOp::Op(Op::CloseUpvalue),
+            BytecodeOp::Op(Op::Loop), BytecodeOp::JumpDistance(13),
+            BytecodeOp::Op(Op::None),
+            BytecodeOp::Op(Op::Return),
+        ];
+        assert_eq!(function_codes(&mut parser), truth);
+        assert!(!parser.had_error);
+    }
+
+    #[test]
+    fn test_local_in_own_initializer() {
+        let mut strings = HashSet::new();
+        let mut parser = function_parser("x = x + 1\n", &mut strings);
+        function_codes(&mut parser);
+        assert_eq!(parser.errors.len(), 1);
+        assert_eq!(parser.errors[0].message, "cannot read local variable in its own initializer");
+
+        // scripts assign globals, so the same line is fine there
+        assert!(crate::compile("x = x + 1\n").is_ok());
+    }
+
+    #[test]
+    fn test_unescape() {
+        let unescaped = |body: &str| unescape(&body.chars().collect::<Vec<char>>());
+        assert_eq!(unescaped(r#"a\tb\n\\\'\""#).as_deref(), Some("a\tb\n\\'\""));
+        assert_eq!(unescaped(r"\x41\u00e9\U0001F600").as_deref(), Some("A\u{e9}\u{1F600}"));
+        assert_eq!(unescaped("\\q line\\\ncontinued").as_deref(), Some("\\q linecontinued"));
+        assert_eq!(unescaped(r"\x4"), None);
+        assert_eq!(unescaped(r"\UFFFFFFFF"), None);
+    }
 }
@@ -1,9 +1,11 @@
 
+use std::cmp::Ordering;
+
 use hashbrown::HashMap;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
-use crate::value::Value;
+use crate::{value::Value, error::{ErrorKind, RuntimeError}};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Hash)]
 pub enum Keyword {
@@ -16,13 +18,24 @@ pub enum Keyword {
     While,
     For,
     Class,
+    True,
+    False,
+    None,
+    And,
+    Or,
+    Pass,
+    Break,
+    Continue,
+    Def,
+    Return,
+    Global,
+    Nonlocal,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Hash)]
 pub enum Operator {
     LeftParen,
     RightParen,
-    Pound,
     Plus,
     Minus,
     Star,
@@ -36,6 +49,11 @@ pub enum Operator {
     LessEqual,
     Greater,
     GreaterEqual,
+    Colon,
+    Comma,
+    LeftBracket,
+    RightBracket,
+    Dot,
 }
 
 
@@ -58,6 +76,18 @@ impl From<Keyword> for &str {
             Keyword::While => "while",
             Keyword::For => "for",
             Keyword::Class => "class",
+            Keyword::True => "True",
+            Keyword::False => "False",
+            Keyword::None => "None",
+            Keyword::And => "and",
+            Keyword::Or => "or",
+            Keyword::Pass => "pass",
+            Keyword::Break => "break",
+            Keyword::Continue => "continue",
+            Keyword::Def => "def",
+            Keyword::Return => "return",
+            Keyword::Global => "global",
+            Keyword::Nonlocal => "nonlocal",
         }
     }
 }
@@ -67,7 +97,6 @@ impl From<Operator> for &str {
         match value {
             Operator::LeftParen => "(",
             Operator::RightParen => ")",
-            Operator::Pound => "#",
             Operator::Plus => "+",
             Operator::Minus => "-",
             Operator::Star => "*",
@@ -81,6 +110,11 @@ impl From<Operator> for &str {
             Operator::Greater => ">",
             Operator::GreaterEqual => ">=",
             Operator::NotEqual => "!=",
+            Operator::Colon => ":",
+            Operator::Comma => ",",
+            Operator::LeftBracket => "[",
+            Operator::RightBracket => "]",
+            Operator::Dot => ".",
         }
     }
 }
@@ -96,7 +130,7 @@ impl From<Number> for &str {
 }
 
 impl Operator {
-    pub fn doit<'a>(&self, a: Value<'a>, b: Value<'a>) -> Value<'a> {
+    pub fn doit(&self, a: Value, b: Value) -> Result<Value, RuntimeError> {
         match self {
             Operator::Plus => a + b,
             Operator::Minus => a - b,
@@ -104,24 +138,68 @@ impl Operator {
             Operator::StarStar => a.pow(b),
             Operator::Slash => a / b,
             Operator::SlashSlash => a.int_division(b),
-            _ => todo!("inafeinf")
-            // Operator::EqualEqual => a == b,
-            // Operator::Greater => a > b,
-            // Operator::GreaterEqual => a >= b,
-            // Operator::Less => a < b,
-            // Operator::LessEqual => a <= b,
-            // Operator::NotEqual => a != b,
+            Operator::EqualEqual => Ok(Value::Bool(a == b)),
+            Operator::NotEqual => Ok(Value::Bool(a != b)),
+            Operator::Greater => Ok(Value::Bool(a.compare(&b, ">")? == Some(Ordering::Greater))),
+            Operator::GreaterEqual => Ok(Value::Bool(matches!(a.compare(&b, ">=")?, Some(Ordering::Greater | Ordering::Equal)))),
+            Operator::Less => Ok(Value::Bool(a.compare(&b, "<")? == Some(Ordering::Less))),
+            Operator::LessEqual => Ok(Value::Bool(matches!(a.compare(&b, "<=")?, Some(Ordering::Less | Ordering::Equal)))),
+            _ => Err(RuntimeError::new(ErrorKind::Internal, format!("{:?} is not a binary operator", self)))
+        }
+    }
+
+    /// The special methods a class can define for the operator: the one
+    /// tried on the left operand, and the reflected one tried on the right.
+    pub fn dunder(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Operator::Plus => Some(("__add__", "__radd__")),
+            Operator::Minus => Some(("__sub__", "__rsub__")),
+            Operator::Star => Some(("__mul__", "__rmul__")),
+            Operator::StarStar => Some(("__pow__", "__rpow__")),
+            Operator::Slash => Some(("__truediv__", "__rtruediv__")),
+            Operator::SlashSlash => Some(("__floordiv__", "__rfloordiv__")),
+            Operator::EqualEqual => Some(("__eq__", "__eq__")),
+            Operator::NotEqual => Some(("__ne__", "__ne__")),
+            Operator::Greater => Some(("__gt__", "__lt__")),
+            Operator::GreaterEqual => Some(("__ge__", "__le__")),
+            Operator::Less => Some(("__lt__", "__gt__")),
+            Operator::LessEqual => Some(("__le__", "__ge__")),
+            _ => None,
         }
     }
 }
 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LexError {
+    UnexpectedCharacter,
+    InconsistentDedent,
+    MixedIndentation,
+    UnterminatedString,
+}
+
+impl From<LexError> for &str {
+    fn from(value: LexError) -> Self {
+        match value {
+            LexError::UnexpectedCharacter => "unexpected character",
+            LexError::InconsistentDedent => "unindent does not match any outer indentation level",
+            LexError::MixedIndentation => "inconsistent use of tabs and spaces in indentation",
+            LexError::UnterminatedString => "unterminated string literal",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TokenType {
     Keyword(Keyword),
     Operator(Operator),
     Number(Number),
     Identifier,
+    String,
+    Newline,
+    Indent,
+    Dedent,
+    Error(LexError),
     Eof
 }
 
@@ -132,6 +210,11 @@ impl From<TokenType> for &str {
             TokenType::Operator(x) => x.into(),
             TokenType::Number(x) => x.into(),
             TokenType::Identifier => "Identifier",
+            TokenType::String => "String",
+            TokenType::Newline => "Newline",
+            TokenType::Indent => "Indent",
+            TokenType::Dedent => "Dedent",
+            TokenType::Error(x) => x.into(),
             TokenType::Eof => "Eof",
         }
     }
@@ -143,7 +226,7 @@ struct KeywordTree {
     children: HashMap<char, KeywordTree>
 }
 
-impl<'source> KeywordTree {
+impl KeywordTree {
     fn add_token(&mut self, tokentype: TokenType) {
         let mut current = self;
 
@@ -200,21 +283,47 @@ pub struct Token {
     pub line: usize
 }
 
+/// A `#` comment kept as trivia, attached to the token that follows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comment {
+    pub start: usize,
+    pub length: usize,
+    pub line: usize,
+    /// Index of the following token in the output of `tokenize`.
+    pub token: usize
+}
+
 #[derive(Debug)]
 pub struct Tokenizer<'source> {
     pub source: &'source str,
     chars: Vec<char>,
     current_index: usize,
     line: usize,
+    /// (tabs as 8 columns, tabs as 1 column) for each open indentation level
+    indents: Vec<(usize, usize)>,
+    paren_depth: usize,
+    /// Only collected after `keep_comments`.
+    comments: Option<Vec<Comment>>,
     keywords: KeywordTree,
     operators: KeywordTree,
 }
 
 impl<'source> Tokenizer<'source> {
-    pub fn new(source: &str) -> Tokenizer {
+    pub fn new(source: &'source str) -> Tokenizer<'source> {
         let chars: Vec<char> = source.chars().collect();
         let (keywords, operators) = KeywordTree::populate();
-        Tokenizer { source, chars, current_index: 0, line: 0, keywords, operators }
+        Tokenizer { source, chars, current_index: 0, line: 0, indents: vec![(0, 0)], paren_depth: 0, comments: None, keywords, operators }
+    }
+
+    /// Keeps comments instead of discarding them; read them with `comments`
+    /// after tokenizing.
+    pub fn keep_comments(mut self) -> Tokenizer<'source> {
+        self.comments = Some(vec![]);
+        self
+    }
+
+    pub fn comments(&self) -> &[Comment] {
+        self.comments.as_deref().unwrap_or_default()
     }
 
     #[inline]
@@ -224,8 +333,9 @@ impl<'source> Tokenizer<'source> {
 
     fn parse_number(&self) -> Token {
         let mut found_dot = false;
+        let mut found_exponent = false;
         let mut found_j = false;
-        let mut length: usize = 1;
+        let mut length: usize = 0;
 
         let mut chars = self.chars[self.current_index..].iter();
         let mut chr = chars.next();
@@ -249,6 +359,7 @@ impl<'source> Tokenizer<'source> {
 
         // optional scientific notation part
         if chr.is_some_and(|&c| c == 'e' || c == 'E') {
+            found_exponent = true;
             length += 1;
             chr = chars.next();
 
@@ -272,7 +383,7 @@ impl<'source> Tokenizer<'source> {
 
         let tokentype = if found_j {
             TokenType::Number(Number::Complex)
-        } else if found_dot {
+        } else if found_dot || found_exponent {
             TokenType::Number(Number::Float)
         } else {
             TokenType::Number(Number::Integer)
@@ -298,10 +409,14 @@ impl<'source> Tokenizer<'source> {
 
         }
 
-        if length == 0 || keyword_node.token.is_none() {
-            None
-        } else {
-            Some(Token {tokentype: keyword_node.token.unwrap(), start: self.current_index, length, line: self.line})
+        // a keyword followed by more identifier characters is just the start of a name
+        if chr.is_some_and(|c| is_digit(c) || is_alpha(c) || is_underscore(c)) {
+            return None;
+        }
+
+        match keyword_node.token {
+            Some(tokentype) if length > 0 => Some(Token {tokentype, start: self.current_index, length, line: self.line}),
+            _ => None
         }
         
     }
@@ -323,10 +438,9 @@ impl<'source> Tokenizer<'source> {
 
         }
 
-        if length == 0 || op_node.token.is_none() {
-            None
-        } else {
-            Some(Token {tokentype: op_node.token.unwrap(), start: self.current_index, length, line: self.line})
+        match op_node.token {
+            Some(tokentype) if length > 0 => Some(Token {tokentype, start: self.current_index, length, line: self.line}),
+            _ => None
         }
         
     }
@@ -344,28 +458,151 @@ impl<'source> Tokenizer<'source> {
         Token {tokentype: TokenType::Identifier, start: self.current_index, length, line: self.line}
     }
 
+    /// Scans a single, double or triple-quoted string literal, quotes included.
+    /// Escapes are left for the parser; only triple-quoted strings and escaped
+    /// newlines may span lines.
+    fn parse_string(&mut self) -> Token {
+        let start = self.current_index;
+        let line = self.line;
+        let quote = self.peek(0).unwrap();
+        let triple = self.peek(1) == Some(quote) && self.peek(2) == Some(quote);
+        let delimiter_length = if triple { 3 } else { 1 };
+
+        self.current_index += delimiter_length;
+        let tokentype = loop {
+            match self.peek(0) {
+                None => break TokenType::Error(LexError::UnterminatedString),
+                Some('\\') => {
+                    if self.peek(1) == Some('\n') {
+                        self.line += 1;
+                    }
+                    self.current_index = (self.current_index + 2).min(self.chars.len());
+                },
+                Some('\n') if !triple => break TokenType::Error(LexError::UnterminatedString),
+                Some('\n') => {
+                    self.line += 1;
+                    self.current_index += 1;
+                },
+                Some(c) if c == quote && (!triple || (self.peek(1) == Some(quote) && self.peek(2) == Some(quote))) => {
+                    self.current_index += delimiter_length;
+                    break TokenType::String;
+                },
+                Some(_) => self.current_index += 1,
+            }
+        };
+
+        Token {tokentype, start, length: self.current_index - start, line}
+    }
+
+    /// Measures the indentation of a new logical line and emits the `Indent`,
+    /// `Dedent` or `Error` tokens it implies. Blank lines are left alone.
+    ///
+    /// Like CPython, indentation is measured twice, with tabs as 8 columns and
+    /// as 1, and both measures have to agree on how lines nest.
+    fn parse_indentation(&mut self, tokens: &mut Vec<Token>) {
+        let start = self.current_index;
+        let mut column: usize = 0;
+        let mut alt_column: usize = 0;
+
+        while let Some(c) = self.peek(0) {
+            match c {
+                ' ' => {
+                    column += 1;
+                    alt_column += 1;
+                },
+                '\t' => {
+                    column = (column / 8 + 1) * 8;
+                    alt_column += 1;
+                },
+                _ => break
+            }
+            self.current_index += 1;
+        }
+
+        // blank and comment-only lines don't affect indentation
+        if matches!(self.peek(0), None | Some('\n') | Some('\r') | Some('#')) {
+            return;
+        }
+
+        let length = self.current_index - start;
+        let mixed = Token {tokentype: TokenType::Error(LexError::MixedIndentation), start, length, line: self.line};
+        let &(top_column, top_alt_column) = self.indents.last().unwrap();
+
+        if column > top_column {
+            if alt_column <= top_alt_column {
+                tokens.push(mixed);
+            }
+            self.indents.push((column, alt_column));
+            tokens.push(Token {tokentype: TokenType::Indent, start, length, line: self.line});
+        } else {
+            while column < self.indents.last().unwrap().0 {
+                self.indents.pop();
+                tokens.push(Token {tokentype: TokenType::Dedent, start: self.current_index, length: 0, line: self.line});
+            }
+
+            let &(top_column, top_alt_column) = self.indents.last().unwrap();
+            if column != top_column {
+                tokens.push(Token {tokentype: TokenType::Error(LexError::InconsistentDedent), start, length, line: self.line});
+            } else if alt_column != top_alt_column {
+                tokens.push(mixed);
+            }
+        }
+    }
+
     pub fn tokenize(&mut self) -> Vec<Token> {
-        // let mut chars = self.chars[self.current_index..].iter();
-        // let mut chr = chars.next();
         let mut tokens: Vec<Token> = vec![];
+        // whether the current logical line has produced a token yet
+        let mut in_line = false;
+        let mut at_line_start = true;
 
         let mut chr = self.peek(0);
         while chr.is_some() {
+            if at_line_start {
+                at_line_start = false;
+                if self.paren_depth == 0 {
+                    self.parse_indentation(&mut tokens);
+                    chr = self.peek(0);
+                    continue;
+                }
+            }
+
             match chr.unwrap() {
                 // non-newline whitespace
-                c if c == ' ' || c == '\t' => {
+                ' ' | '\t' | '\r' => {
                     self.current_index += 1;
                 },
-                // newline whitespace
-                c if c == '\n' => {
+                // newline whitespace, which ends a logical line outside of brackets
+                '\n' => {
+                    if in_line && self.paren_depth == 0 {
+                        tokens.push(Token {tokentype: TokenType::Newline, start: self.current_index, length: 1, line: self.line});
+                        in_line = false;
+                    }
                     self.line += 1;
                     self.current_index += 1;
+                    at_line_start = true;
                 },
                 // numbers
                 c if is_digit(c) => {
                     let token = self.parse_number();
                     tokens.push(token);
-                    self.current_index += token.length;              
+                    self.current_index += token.length;
+                    in_line = true;
+                },
+                // comments, which run to the end of the line
+                '#' => {
+                    let start = self.current_index;
+                    while self.peek(0).is_some_and(|c| c != '\n') {
+                        self.current_index += 1;
+                    }
+                    if let Some(comments) = self.comments.as_mut() {
+                        comments.push(Comment {start, length: self.current_index - start, line: self.line, token: tokens.len()});
+                    }
+                },
+                // strings
+                '\'' | '"' => {
+                    let token = self.parse_string();
+                    tokens.push(token);
+                    in_line = true;
                 },
                 // identifiers
                 c if is_alpha(c) || is_underscore(c) => {
@@ -383,24 +620,40 @@ impl<'source> Tokenizer<'source> {
                             self.current_index += identifier.length;                              
                         }
                     }
+                    in_line = true;
                 },
                 // operators
                 _ => {
                     let reserved = self.parse_operators();
                     match reserved {
                         Some(t) => {
+                            match t.tokentype {
+                                TokenType::Operator(Operator::LeftParen | Operator::LeftBracket) => self.paren_depth += 1,
+                                TokenType::Operator(Operator::RightParen | Operator::RightBracket) => self.paren_depth = self.paren_depth.saturating_sub(1),
+                                _ => {}
+                            }
                             tokens.push(t);
                             self.current_index += t.length;
                          },
                         None => {
-                            panic!("unknown token");
+                            tokens.push(Token {tokentype: TokenType::Error(LexError::UnexpectedCharacter), start: self.current_index, length: 1, line: self.line});
+                            self.current_index += 1;
                         }
                     }
+                    in_line = true;
                 }
             };
             chr = self.peek(0);
        }
 
+        if in_line {
+            tokens.push(Token {tokentype: TokenType::Newline, start: self.current_index, length: 0, line: self.line});
+        }
+        while self.indents.len() > 1 {
+            self.indents.pop();
+            tokens.push(Token {tokentype: TokenType::Dedent, start: self.current_index, length: 0, line: self.line});
+        }
+        tokens.push(Token {tokentype: TokenType::Eof, start: self.current_index, length: 0, line: self.line});
         tokens
 
     }
@@ -409,18 +662,136 @@ impl<'source> Tokenizer<'source> {
 
 #[cfg(test)]
 mod tests {
-    use super::{Token, Tokenizer, Number, TokenType};
+    use super::{Token, Tokenizer, Number, TokenType, Operator, Keyword, LexError, Comment};
 
     #[test]
     fn test_basic_number_parsing() {
         let tokens = Tokenizer::new(r"1.2 21 2.1J 1e-3j 1e-1").tokenize();
-        let truth = vec![	
-            Token { tokentype: TokenType::Number(Number::Float), start: 0, length: 4, line: 0 },
-            Token { tokentype: TokenType::Number(Number::Integer), start: 4, length: 3, line: 0 },
-            Token { tokentype: TokenType::Number(Number::Complex), start: 7, length: 5, line: 0 },
-            Token { tokentype: TokenType::Number(Number::Complex), start: 12, length: 6, line: 0 },
-            Token { tokentype: TokenType::Number(Number::Integer), start: 18, length: 5, line: 0 },
+        let truth = [
+            Token { tokentype: TokenType::Number(Number::Float), start: 0, length: 3, line: 0 },
+            Token { tokentype: TokenType::Number(Number::Integer), start: 4, length: 2, line: 0 },
+            Token { tokentype: TokenType::Number(Number::Complex), start: 7, length: 4, line: 0 },
+            Token { tokentype: TokenType::Number(Number::Complex), start: 12, length: 5, line: 0 },
+            Token { tokentype: TokenType::Number(Number::Float), start: 18, length: 4, line: 0 },
+            Token { tokentype: TokenType::Newline, start: 22, length: 0, line: 0 },
+            Token { tokentype: TokenType::Eof, start: 22, length: 0, line: 0 },
         ];
-        assert!(tokens.iter().zip(truth.iter()).map(|(&x, &y)| x == y).all(|x| x));
+        assert_eq!(tokens, truth);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_unexpected_characters() {
+        let tokens = Tokenizer::new("1 @ $2 +").tokenize();
+        let truth = [
+            Token { tokentype: TokenType::Number(Number::Integer), start: 0, length: 1, line: 0 },
+            Token { tokentype: TokenType::Error(LexError::UnexpectedCharacter), start: 2, length: 1, line: 0 },
+            Token { tokentype: TokenType::Error(LexError::UnexpectedCharacter), start: 4, length: 1, line: 0 },
+            Token { tokentype: TokenType::Number(Number::Integer), start: 5, length: 1, line: 0 },
+            Token { tokentype: TokenType::Operator(Operator::Plus), start: 7, length: 1, line: 0 },
+            Token { tokentype: TokenType::Newline, start: 8, length: 0, line: 0 },
+            Token { tokentype: TokenType::Eof, start: 8, length: 0, line: 0 },
+        ];
+        assert_eq!(tokens, truth);
+    }
+
+    #[test]
+    fn test_strings() {
+        let tokens = Tokenizer::new("'a' \"b\\\"\" '''c\nd'''\n\"\"\"\"\"\"").tokenize();
+        let truth = [
+            Token { tokentype: TokenType::String, start: 0, length: 3, line: 0 },
+            Token { tokentype: TokenType::String, start: 4, length: 5, line: 0 },
+            Token { tokentype: TokenType::String, start: 10, length: 9, line: 0 },
+            Token { tokentype: TokenType::Newline, start: 19, length: 1, line: 1 },
+            Token { tokentype: TokenType::String, start: 20, length: 6, line: 2 },
+            Token { tokentype: TokenType::Newline, start: 26, length: 0, line: 2 },
+            Token { tokentype: TokenType::Eof, start: 26, length: 0, line: 2 },
+        ];
+        assert_eq!(tokens, truth);
+
+        let truth = [
+            TokenType::Error(LexError::UnterminatedString), TokenType::Newline,
+            TokenType::Identifier, TokenType::Error(LexError::UnterminatedString), TokenType::Newline,
+            TokenType::Eof,
+        ];
+        assert_eq!(tokentypes("'abc\nx '''y\n"), truth);
+    }
+
+    #[test]
+    fn test_comments() {
+        let truth = [
+            TokenType::Identifier, TokenType::Newline,
+            TokenType::Indent, TokenType::Identifier, TokenType::Newline,
+            TokenType::Dedent, TokenType::String, TokenType::Newline,
+            TokenType::Eof,
+        ];
+        assert_eq!(tokentypes("x # one\n  y\n# two\n      # three\n'#'\n"), truth);
+
+        let mut tokenizer = Tokenizer::new("# a\nx  # b\n").keep_comments();
+        let tokens = tokenizer.tokenize();
+        let truth = [
+            Comment { start: 0, length: 3, line: 0, token: 0 },
+            Comment { start: 7, length: 3, line: 1, token: 1 },
+        ];
+        assert_eq!(tokenizer.comments(), truth);
+        assert_eq!(tokens[1].tokentype, TokenType::Newline);
+        assert!(Tokenizer::new("# a").comments().is_empty());
+    }
+
+    #[test]
+    fn test_keywords_are_whole_words() {
+        let truth = [
+            TokenType::Identifier, TokenType::Identifier, TokenType::Identifier, TokenType::Identifier,
+            TokenType::Keyword(Keyword::Not), TokenType::Keyword(Keyword::Or), TokenType::Identifier,
+            TokenType::Newline, TokenType::Eof,
+        ];
+        assert_eq!(tokentypes("index order andy None_ not or nothing"), truth);
+    }
+
+    fn tokentypes(source: &str) -> Vec<TokenType> {
+        Tokenizer::new(source).tokenize().iter().map(|t| t.tokentype).collect()
+    }
+
+    #[test]
+    fn test_indentation() {
+        let truth = [
+            TokenType::Identifier, TokenType::Newline,
+            TokenType::Indent, TokenType::Identifier, TokenType::Newline,
+            TokenType::Indent, TokenType::Identifier, TokenType::Newline,
+            TokenType::Dedent, TokenType::Identifier, TokenType::Newline,
+            TokenType::Dedent, TokenType::Identifier, TokenType::Newline,
+            TokenType::Indent, TokenType::Identifier, TokenType::Newline,
+            TokenType::Dedent, TokenType::Eof,
+        ];
+        assert_eq!(tokentypes("x\n  y\n    z\n\n   \n  w\nv\n\tu"), truth);
+    }
+
+    #[test]
+    fn test_newlines_inside_parens() {
+        let truth = [
+            TokenType::Operator(Operator::LeftParen), TokenType::Number(Number::Integer), TokenType::Operator(Operator::Plus),
+            TokenType::Number(Number::Integer), TokenType::Operator(Operator::RightParen), TokenType::Newline,
+            TokenType::Number(Number::Integer), TokenType::Newline,
+            TokenType::Eof,
+        ];
+        assert_eq!(tokentypes("(1 +\n        2)\n3\n"), truth);
+    }
+
+    #[test]
+    fn test_indentation_errors() {
+        let truth = [
+            TokenType::Identifier, TokenType::Newline,
+            TokenType::Indent, TokenType::Identifier, TokenType::Newline,
+            TokenType::Dedent, TokenType::Error(LexError::InconsistentDedent), TokenType::Identifier, TokenType::Newline,
+            TokenType::Eof,
+        ];
+        assert_eq!(tokentypes("x\n    y\n  z\n"), truth);
+
+        let truth = [
+            TokenType::Identifier, TokenType::Newline,
+            TokenType::Indent, TokenType::Identifier, TokenType::Newline,
+            TokenType::Error(LexError::MixedIndentation), TokenType::Identifier, TokenType::Newline,
+            TokenType::Dedent, TokenType::Eof,
+        ];
+        assert_eq!(tokentypes("x\n\ty\n        z\n"), truth);
+    }
+}
@@ -1,156 +1,815 @@
 
-use std::ops::{Add, Sub, Mul, Div};
+use std::{borrow::Cow, cell::RefCell, cmp::Ordering, fmt, ops::{Add, Sub, Mul, Div, Neg}, rc::Rc};
 
-use num::complex::Complex;
+use num::{complex::Complex, BigInt, BigRational, Integer, Signed, ToPrimitive, Zero, pow::Pow};
 
-use crate::object::ObjFucntion;
+use crate::{object::{Object, ObjBoundMethod, ObjIterator}, error::{ErrorKind, RuntimeError}};
 
 
-#[derive(Debug, Clone, Copy)]
-pub enum Value<'vm> {
+#[derive(Debug, Clone)]
+pub enum Value {
+    None,
     Bool(bool),
     Integer(i32),
+    /// Integers outside the `i32` range; always normalized back to `Integer`
+    /// when they fit.
+    BigInt(Rc<BigInt>),
+    Rational(Rc<BigRational>),
     Float(f64),
     Complex(Complex<f64>),
-    Object(&'vm ObjFucntion<'vm>)
+    Object(Object)
 }
 
-impl<'a> Add<Value<'a>> for Value<'a> {
-    type Output = Value<'a>;
-    fn add(self, rhs: Value) -> Self {
+/// Formats one part of a complex number the way Python does, dropping the
+/// trailing `.0` from integral parts.
+fn fmt_complex_part(x: f64) -> String {
+    if x.is_finite() && x.fract() == 0.0 && x.abs() < 1e16 {
+        format!("{}", x as i64)
+    } else {
+        fmt_float(x)
+    }
+}
+
+fn fmt_float(x: f64) -> String {
+    if x.is_nan() {
+        String::from("nan")
+    } else if x.is_infinite() {
+        String::from(if x > 0.0 { "inf" } else { "-inf" })
+    } else {
+        format!("{:?}", x)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::None => write!(f, "None"),
+            Value::Bool(true) => write!(f, "True"),
+            Value::Bool(false) => write!(f, "False"),
+            Value::Integer(x) => write!(f, "{}", x),
+            Value::BigInt(x) => write!(f, "{}", x),
+            Value::Rational(x) => write!(f, "{}", x),
+            Value::Float(x) => write!(f, "{}", fmt_float(*x)),
+            Value::Complex(x) if x.re == 0.0 && x.re.is_sign_positive() => write!(f, "{}j", fmt_complex_part(x.im)),
+            Value::Complex(x) => {
+                let sign = if x.im.is_sign_negative() { "-" } else { "+" };
+                write!(f, "({}{}{}j)", fmt_complex_part(x.re), sign, fmt_complex_part(x.im.abs()))
+            }
+            Value::Object(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl From<BigInt> for Value {
+    fn from(value: BigInt) -> Self {
+        match value.to_i32() {
+            Some(x) => Value::Integer(x),
+            None => Value::BigInt(Rc::new(value)),
+        }
+    }
+}
+
+impl From<BigRational> for Value {
+    fn from(value: BigRational) -> Self {
+        Value::Rational(Rc::new(value))
+    }
+}
+
+impl Add<Value> for Value {
+    type Output = Result<Value, RuntimeError>;
+    fn add(self, rhs: Value) -> Self::Output {
+        if let Some((a, b)) = promote_bools(&self, &rhs) {
+            return a + b;
+        }
         match (self, rhs) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a + b),
-            // (Value::Integer(a), Value::Float(b)) => Value::Float(a as f64 + b),
-            // (Value::Integer(a), Value::Complex(b)) => Value::Complex(Complex::new(a as f64, 0.0) + b),
-            // (Value::Float(a), Value::Integer(b)) => Value::Float(a + b as f64),
-            (Value::Float(a), Value::Float(b)) => Value::Float(a + b),
-            // (Value::Float(a), Value::Complex(b)) => Value::Complex(Complex::new(a, 0.0) + b),
-            // (Value::Complex(a), Value::Integer(b)) => Value::Complex(a + Complex::new(b as f64, 0.0)),
-            // (Value::Complex(a), Value::Float(b)) => Value::Complex(a + Complex::new(b, 0.0)),
-            (Value::Complex(a), Value::Complex(b)) => Value::Complex(a + b),
-            _ => unreachable!()
+            (Value::Integer(a), Value::Integer(b)) => Ok(a.checked_add(b).map_or_else(|| Value::from(BigInt::from(a) + b), Value::Integer)),
+            (Value::Integer(a), Value::Float(b)) => Ok(Value::Float(a as f64 + b)),
+            (Value::Integer(a), Value::Complex(b)) => Ok(Value::Complex(Complex::new(a as f64, 0.0) + b)),
+            (Value::Float(a), Value::Integer(b)) => Ok(Value::Float(a + b as f64)),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
+            (Value::Float(a), Value::Complex(b)) => Ok(Value::Complex(Complex::new(a, 0.0) + b)),
+            (Value::Complex(a), Value::Integer(b)) => Ok(Value::Complex(a + Complex::new(b as f64, 0.0))),
+            (Value::Complex(a), Value::Float(b)) => Ok(Value::Complex(a + Complex::new(b, 0.0))),
+            (Value::Complex(a), Value::Complex(b)) => Ok(Value::Complex(a + b)),
+            (Value::BigInt(a), b @ (Value::Float(_) | Value::Complex(_))) => Value::Float(big_to_float(&a)?) + b,
+            (a @ (Value::Float(_) | Value::Complex(_)), Value::BigInt(b)) => a + Value::Float(big_to_float(&b)?),
+            (Value::Rational(a), b @ (Value::Float(_) | Value::Complex(_))) => Value::Float(rational_to_float(&a)?) + b,
+            (a @ (Value::Float(_) | Value::Complex(_)), Value::Rational(b)) => a + Value::Float(rational_to_float(&b)?),
+            (Value::Object(Object::String(a)), Value::Object(Object::String(b))) => Ok(Value::string([&*a, &*b].concat())),
+            (a, b) => exact("+", a, b, |x, y| Ok(Value::from(x + y)), |x, y| Ok(Value::from(x + y)))
         }
     }
 }
 
-impl<'a> Sub<Value<'a>> for Value<'a> {
-    type Output = Value<'a>;
+impl Sub<Value> for Value {
+    type Output = Result<Value, RuntimeError>;
     fn sub(self, rhs: Value) -> Self::Output {
+        if let Some((a, b)) = promote_bools(&self, &rhs) {
+            return a - b;
+        }
         match (self, rhs) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a - b),
-            // (Value::Integer(a), Value::Float(b)) => Value::Float(a as f64 - b),
-            // (Value::Integer(a), Value::Complex(b)) => Value::Complex(Complex::new(a as f64, 0.0) - b),
-            // (Value::Float(a), Value::Integer(b)) => Value::Float(a - b as f64),
-            (Value::Float(a), Value::Float(b)) => Value::Float(a - b),
-            // (Value::Float(a), Value::Complex(b)) => Value::Complex(Complex::new(a, 0.0) - b),
-            // (Value::Complex(a), Value::Integer(b)) => Value::Complex(a - Complex::new(b as f64, 0.0)),
-            // (Value::Complex(a), Value::Float(b)) => Value::Complex(a - Complex::new(b, 0.0)),
-            (Value::Complex(a), Value::Complex(b)) => Value::Complex(a - b),
-            _ => unreachable!()
+            (Value::Integer(a), Value::Integer(b)) => Ok(a.checked_sub(b).map_or_else(|| Value::from(BigInt::from(a) - b), Value::Integer)),
+            (Value::Integer(a), Value::Float(b)) => Ok(Value::Float(a as f64 - b)),
+            (Value::Integer(a), Value::Complex(b)) => Ok(Value::Complex(Complex::new(a as f64, 0.0) - b)),
+            (Value::Float(a), Value::Integer(b)) => Ok(Value::Float(a - b as f64)),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
+            (Value::Float(a), Value::Complex(b)) => Ok(Value::Complex(Complex::new(a, 0.0) - b)),
+            (Value::Complex(a), Value::Integer(b)) => Ok(Value::Complex(a - Complex::new(b as f64, 0.0))),
+            (Value::Complex(a), Value::Float(b)) => Ok(Value::Complex(a - Complex::new(b, 0.0))),
+            (Value::Complex(a), Value::Complex(b)) => Ok(Value::Complex(a - b)),
+            (Value::BigInt(a), b @ (Value::Float(_) | Value::Complex(_))) => Value::Float(big_to_float(&a)?) - b,
+            (a @ (Value::Float(_) | Value::Complex(_)), Value::BigInt(b)) => a - Value::Float(big_to_float(&b)?),
+            (Value::Rational(a), b @ (Value::Float(_) | Value::Complex(_))) => Value::Float(rational_to_float(&a)?) - b,
+            (a @ (Value::Float(_) | Value::Complex(_)), Value::Rational(b)) => a - Value::Float(rational_to_float(&b)?),
+            (a, b) => exact("-", a, b, |x, y| Ok(Value::from(x - y)), |x, y| Ok(Value::from(x - y)))
         }
     }
 }
 
-impl<'a> Mul<Value<'a>> for Value<'a> {
-    type Output = Value<'a>;
+impl Mul<Value> for Value {
+    type Output = Result<Value, RuntimeError>;
     fn mul(self, rhs: Value) -> Self::Output {
+        if let Some((a, b)) = promote_bools(&self, &rhs) {
+            return a * b;
+        }
         match (self, rhs) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a * b),
-            // (Value::Integer(a), Value::Float(b)) => Value::Float(a as f64 * b),
-            // (Value::Integer(a), Value::Complex(b)) => Value::Complex(Complex::new(a as f64, 0.0) * b),
-            // (Value::Float(a), Value::Integer(b)) => Value::Float(a * b as f64),
-            (Value::Float(a), Value::Float(b)) => Value::Float(a * b),
-            // (Value::Float(a), Value::Complex(b)) => Value::Complex(Complex::new(a, 0.0) * b),
-            // (Value::Complex(a), Value::Integer(b)) => Value::Complex(a * Complex::new(b as f64, 0.0)),
-            // (Value::Complex(a), Value::Float(b)) => Value::Complex(a * Complex::new(b, 0.0)),
-            (Value::Complex(a), Value::Complex(b)) => Value::Complex(a * b),
-            _ => unreachable!()
+            (Value::Integer(a), Value::Integer(b)) => Ok(a.checked_mul(b).map_or_else(|| Value::from(BigInt::from(a) * b), Value::Integer)),
+            (Value::Integer(a), Value::Float(b)) => Ok(Value::Float(a as f64 * b)),
+            (Value::Integer(a), Value::Complex(b)) => Ok(Value::Complex(Complex::new(a as f64, 0.0) * b)),
+            (Value::Float(a), Value::Integer(b)) => Ok(Value::Float(a * b as f64)),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
+            (Value::Float(a), Value::Complex(b)) => Ok(Value::Complex(Complex::new(a, 0.0) * b)),
+            (Value::Complex(a), Value::Integer(b)) => Ok(Value::Complex(a * Complex::new(b as f64, 0.0))),
+            (Value::Complex(a), Value::Float(b)) => Ok(Value::Complex(a * Complex::new(b, 0.0))),
+            (Value::Complex(a), Value::Complex(b)) => Ok(Value::Complex(a * b)),
+            (Value::BigInt(a), b @ (Value::Float(_) | Value::Complex(_))) => Value::Float(big_to_float(&a)?) * b,
+            (a @ (Value::Float(_) | Value::Complex(_)), Value::BigInt(b)) => a * Value::Float(big_to_float(&b)?),
+            (Value::Rational(a), b @ (Value::Float(_) | Value::Complex(_))) => Value::Float(rational_to_float(&a)?) * b,
+            (a @ (Value::Float(_) | Value::Complex(_)), Value::Rational(b)) => a * Value::Float(rational_to_float(&b)?),
+            (Value::Object(Object::String(a)), Value::Bool(b)) | (Value::Bool(b), Value::Object(Object::String(a))) => {
+                Ok(if b { Value::Object(Object::String(a)) } else { Value::string("") })
+            }
+            (Value::Object(Object::String(a)), Value::Integer(b)) | (Value::Integer(b), Value::Object(Object::String(a))) => {
+                let count = usize::try_from(b).unwrap_or(0);
+                if a.len().checked_mul(count).is_none() {
+                    return Err(RuntimeError::new(ErrorKind::Overflow, "repeated string is too long"));
+                }
+                Ok(Value::string(a.repeat(count)))
+            }
+            (Value::Object(Object::String(_)), Value::BigInt(b)) | (Value::BigInt(b), Value::Object(Object::String(_))) => {
+                if b.is_negative() {
+                    Ok(Value::string(""))
+                } else {
+                    Err(RuntimeError::new(ErrorKind::Overflow, "repeated string is too long"))
+                }
+            }
+            (a, b) => exact("*", a, b, |x, y| Ok(Value::from(x * y)), |x, y| Ok(Value::from(x * y)))
         }
     }
 }
 
-impl<'a> Div<Value<'a>> for Value<'a> {
-    type Output = Value<'a>;
+impl Div<Value> for Value {
+    type Output = Result<Value, RuntimeError>;
     fn div(self, rhs: Value) -> Self::Output {
+        if let Some((a, b)) = promote_bools(&self, &rhs) {
+            return a / b;
+        }
         match (self, rhs) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Float((a / b) as f64),
-            // (Value::Integer(a), Value::Float(b)) => Value::Float(a as f64 / b),
-            // (Value::Integer(a), Value::Complex(b)) => Value::Complex(Complex::new(a as f64, 0.0) / b),
-            // (Value::Float(a), Value::Integer(b)) => Value::Float(a / b as f64),
-            (Value::Float(a), Value::Float(b)) => Value::Float(a / b),
-            // (Value::Float(a), Value::Complex(b)) => Value::Complex(Complex::new(a, 0.0) / b),
-            // (Value::Complex(a), Value::Integer(b)) => Value::Complex(a / Complex::new(b as f64, 0.0)),
-            // (Value::Complex(a), Value::Float(b)) => Value::Complex(a / Complex::new(b, 0.0)),
-            (Value::Complex(a), Value::Complex(b)) => Value::Complex(a / b),
-            _ => unreachable!()
-        }
-    }
-}
-
-// impl<'a> PartialOrd for Value<'a> {
-//     fn ge(&self, other: &Self) -> bool {
-        
-//     }
-// }
-
-// impl<'a> PartialEq for Value<'a> {
-//     fn eq(&self, other: &Self) -> bool {
-        
-//     }
-
-//     fn ne(&self, other: &Self) -> bool {
-        
-//     }
-    
-//     // type Output = Value<'a>;
-//     // fn eq(self, rhs: Value) -> Self:: {
-//     //     match (self, rhs) {
-//     //         (Value::Integer(a), Value::Integer(b)) => Value::Bool(a == b),
-//     //         (Value::Integer(a), Value::Float(b)) => Value::Float(a as f64 / b),
-//     //         (Value::Integer(a), Value::Complex(b)) => Value::Complex(Complex::new(a as f64, 0.0) / b),
-//     //         (Value::Float(a), Value::Integer(b)) => Value::Float(a / b as f64),
-//     //         (Value::Float(a), Value::Float(b)) => Value::Float(a / b),
-//     //         (Value::Float(a), Value::Complex(b)) => Value::Complex(Complex::new(a, 0.0) / b),
-//     //         (Value::Complex(a), Value::Integer(b)) => Value::Complex(a / Complex::new(b as f64, 0.0)),
-//     //         (Value::Complex(a), Value::Float(b)) => Value::Complex(a / Complex::new(b, 0.0)),
-//     //         (Value::Complex(a), Value::Complex(b)) => Value::Complex(a / b),
-//     //         _ => unreachable!()
-//     //     }
-//     // }
-// }
-
-impl<'a> Value<'a> {
-    pub fn pow(self, rhs: Value) -> Self {
+            (Value::Integer(_) | Value::BigInt(_) | Value::Rational(_), Value::Integer(0)) => Err(zero_division("division by zero")),
+            (Value::Integer(_) | Value::BigInt(_) | Value::Rational(_) | Value::Float(_), Value::Integer(0) | Value::Float(0.0)) => Err(zero_division("float division by zero")),
+            (Value::Complex(_), Value::Integer(0) | Value::Float(0.0)) => Err(zero_division("complex division by zero")),
+            (_, Value::Complex(b)) if b == Complex::new(0.0, 0.0) => Err(zero_division("complex division by zero")),
+            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Float(a as f64 / b as f64)),
+            (Value::Integer(a), Value::Float(b)) => Ok(Value::Float(a as f64 / b)),
+            (Value::Integer(a), Value::Complex(b)) => Ok(Value::Complex(Complex::new(a as f64, 0.0) / b)),
+            (Value::Float(a), Value::Integer(b)) => Ok(Value::Float(a / b as f64)),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a / b)),
+            (Value::Float(a), Value::Complex(b)) => Ok(Value::Complex(Complex::new(a, 0.0) / b)),
+            (Value::Complex(a), Value::Integer(b)) => Ok(Value::Complex(a / Complex::new(b as f64, 0.0))),
+            (Value::Complex(a), Value::Float(b)) => Ok(Value::Complex(a / Complex::new(b, 0.0))),
+            (Value::Complex(a), Value::Complex(b)) => Ok(Value::Complex(a / b)),
+            (Value::BigInt(a), b @ (Value::Float(_) | Value::Complex(_))) => Value::Float(big_to_float(&a)?) / b,
+            (a @ (Value::Float(_) | Value::Complex(_)), Value::BigInt(b)) => a / Value::Float(big_to_float(&b)?),
+            (Value::Rational(a), b @ (Value::Float(_) | Value::Complex(_))) => Value::Float(rational_to_float(&a)?) / b,
+            (a @ (Value::Float(_) | Value::Complex(_)), Value::Rational(b)) => a / Value::Float(rational_to_float(&b)?),
+            // dividing exactly first lets huge integers still give a finite quotient
+            (a, b) => exact("/", a, b, |x, y| rational_to_float(&BigRational::new(x, y)).map(Value::Float), |x, y| {
+                if y.is_zero() {
+                    Err(zero_division("division by zero"))
+                } else {
+                    Ok(Value::from(x / y))
+                }
+            })
+        }
+    }
+}
+
+impl Neg for Value {
+    type Output = Result<Value, RuntimeError>;
+    fn neg(self) -> Self::Output {
+        match self {
+            Value::Bool(a) => Ok(Value::Integer(-(a as i32))),
+            Value::Integer(a) => Ok(a.checked_neg().map_or_else(|| Value::from(-BigInt::from(a)), Value::Integer)),
+            Value::BigInt(a) => Ok(Value::from(-BigInt::clone(&a))),
+            Value::Rational(a) => Ok(Value::from(-BigRational::clone(&a))),
+            Value::Float(a) => Ok(Value::Float(-a)),
+            Value::Complex(a) => Ok(Value::Complex(-a)),
+            a => Err(bad_unary("-", &a)),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    /// Python's `==`: numbers compare by value across types, other objects
+    /// by contents (strings) or identity.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::None, Value::None) => true,
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Complex(a), Value::Complex(b)) => a == b,
+            (Value::Complex(a), b) | (b, Value::Complex(a)) => a.im == 0.0 && Value::Float(a.re) == *b,
+            (Value::Object(Object::String(a)), Value::Object(Object::String(b))) => Rc::ptr_eq(a, b) || a == b,
+            (Value::Object(Object::BoundMethod(a)), Value::Object(Object::BoundMethod(b))) => Rc::ptr_eq(a, b),
+            (Value::Object(Object::Class(a)), Value::Object(Object::Class(b))) => Rc::ptr_eq(a, b),
+            (Value::Object(Object::Closure(a)), Value::Object(Object::Closure(b))) => Rc::ptr_eq(a, b),
+            (Value::Object(Object::Instance(a)), Value::Object(Object::Instance(b))) => Rc::ptr_eq(a, b),
+            (Value::Object(Object::Function(a)), Value::Object(Object::Function(b))) => Rc::ptr_eq(a, b),
+            (Value::Object(Object::Native(a)), Value::Object(Object::Native(b))) => Rc::ptr_eq(a, b),
+            (Value::Object(Object::Iterator(a)), Value::Object(Object::Iterator(b))) => Rc::ptr_eq(a, b),
+            (Value::Object(Object::Sentinel(a)), Value::Object(Object::Sentinel(b))) => a == b,
+            (Value::Object(Object::List(a)), Value::Object(Object::List(b))) => Rc::ptr_eq(a, b) || *a.borrow() == *b.borrow(),
+            // ranges are equal when they produce the same integers
+            (Value::Object(Object::Range(a)), Value::Object(Object::Range(b))) => {
+                a.len() == b.len() && (a.is_empty() || a.start == b.start && (a.len() == 1 || a.step == b.step))
+            }
+            (a, b) => match (a.to_real(), b.to_real()) {
+                (Some(x), Some(y)) => compare_reals(x, y) == Some(Ordering::Equal),
+                _ => false,
+            }
+        }
+    }
+}
+
+/// A real number in the form it can be compared exactly in.
+enum Real {
+    Exact(BigRational),
+    Float(f64),
+}
+
+fn compare_reals(a: Real, b: Real) -> Option<Ordering> {
+    match (a, b) {
+        (Real::Exact(x), Real::Exact(y)) => Some(x.cmp(&y)),
+        (Real::Float(x), Real::Float(y)) => x.partial_cmp(&y),
+        (Real::Exact(x), Real::Float(y)) => compare_exact_float(&x, y),
+        (Real::Float(x), Real::Exact(y)) => compare_exact_float(&y, x).map(Ordering::reverse),
+    }
+}
+
+/// Compares without rounding `a` to a float, so `2**53 + 1 != 2.0**53`.
+fn compare_exact_float(a: &BigRational, b: f64) -> Option<Ordering> {
+    if b.is_nan() {
+        None
+    } else if b.is_infinite() {
+        Some(if b > 0.0 { Ordering::Less } else { Ordering::Greater })
+    } else {
+        BigRational::from_float(b).map(|b| a.cmp(&b))
+    }
+}
+
+impl Value {
+    pub fn string(string: impl Into<Rc<str>>) -> Value {
+        Value::Object(Object::String(string.into()))
+    }
+
+    /// Unary `+`, which only accepts numbers.
+    pub fn positive(self) -> Result<Value, RuntimeError> {
+        match self {
+            Value::Bool(a) => Ok(Value::Integer(a as i32)),
+            a if is_number(&a) => Ok(a),
+            a => Err(bad_unary("+", &a)),
+        }
+    }
+
+    /// Python truthiness: zero numbers, empty strings, `None` and `False` are
+    /// false, everything else is true.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::None => false,
+            Value::Bool(x) => *x,
+            Value::Integer(x) => *x != 0,
+            Value::BigInt(x) => !x.is_zero(),
+            Value::Rational(x) => !x.is_zero(),
+            Value::Float(x) => *x != 0.0,
+            Value::Complex(x) => !x.is_zero(),
+            Value::Object(Object::String(x)) => !x.is_empty(),
+            Value::Object(Object::List(x)) => !x.borrow().is_empty(),
+            Value::Object(Object::Range(x)) => !x.is_empty(),
+            Value::Object(_) => true,
+        }
+    }
+
+    /// Orders two values for `<`, `<=`, `>` and `>=`; `None` means unordered,
+    /// as with NaN.
+    pub fn compare(&self, other: &Value, op: &str) -> Result<Option<Ordering>, RuntimeError> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => Ok(a.partial_cmp(b)),
+            (Value::Float(a), Value::Float(b)) => Ok(a.partial_cmp(b)),
+            (Value::Object(Object::String(a)), Value::Object(Object::String(b))) => Ok(a.partial_cmp(b)),
+            (a, b) => match (a.to_real(), b.to_real()) {
+                (Some(x), Some(y)) => Ok(compare_reals(x, y)),
+                _ => Err(RuntimeError::new(
+                    ErrorKind::Type,
+                    format!("'{}' not supported between instances of '{}' and '{}'", op, a.type_name(), b.type_name())
+                ))
+            }
+        }
+    }
+
+    pub fn pow(self, rhs: Value) -> Result<Value, RuntimeError> {
+        if let Some((a, b)) = promote_bools(&self, &rhs) {
+            return a.pow(b);
+        }
         match (self, rhs) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Float((a as f64).powf(b as f64)),
-            // (Value::Integer(a), Value::Float(b)) => Value::Float((a as f64).powf(b)),
-            // (Value::Integer(a), Value::Complex(b)) => Value::Complex(Complex::new(a as f64, 0.0).powc(b)),
-            (Value::Float(a), Value::Integer(b)) => Value::Float(a.powf(b as f64)),
-            (Value::Float(a), Value::Float(b)) => Value::Float(a.powf(b)),
-            // (Value::Float(a), Value::Complex(b)) => Value::Complex(Complex::new(a, 0.0).powc(b)),
-            (Value::Complex(a), Value::Integer(b)) => Value::Complex(a.powc(Complex::new(b as f64, 0.0))),
-            // (Value::Complex(a), Value::Float(b)) => Value::Complex(a.powc(Complex::new(b, 0.0))),
-            (Value::Complex(a), Value::Complex(b)) => Value::Complex(a.powc(b)),
-            _ => unreachable!()
+            (Value::Integer(a), Value::Integer(b)) if b >= 0 => Ok(a.checked_pow(b as u32).map_or_else(|| Value::from(BigInt::from(a).pow(b as u32)), Value::Integer)),
+            (Value::Integer(a), Value::Float(b)) => float_pow(a as f64, b),
+            (Value::Integer(a), Value::Complex(b)) => complex_pow(Complex::new(a as f64, 0.0), b),
+            (Value::Float(a), Value::Integer(b)) => float_pow(a, b as f64),
+            (Value::Float(a), Value::Float(b)) => float_pow(a, b),
+            (Value::Float(a), Value::Complex(b)) => complex_pow(Complex::new(a, 0.0), b),
+            (Value::Complex(a), Value::Integer(b)) => complex_pow(a, Complex::new(b as f64, 0.0)),
+            (Value::Complex(a), Value::Float(b)) => complex_pow(a, Complex::new(b, 0.0)),
+            (Value::Complex(a), Value::Complex(b)) => complex_pow(a, b),
+            (Value::BigInt(a), b @ (Value::Float(_) | Value::Complex(_))) => Value::Float(big_to_float(&a)?).pow(b),
+            (a @ (Value::Float(_) | Value::Complex(_)), Value::BigInt(b)) => a.pow(Value::Float(big_to_float(&b)?)),
+            (Value::Rational(a), b @ (Value::Float(_) | Value::Complex(_))) => Value::Float(rational_to_float(&a)?).pow(b),
+            (a @ (Value::Float(_) | Value::Complex(_)), Value::Rational(b)) => a.pow(Value::Float(rational_to_float(&b)?)),
+            (a, b) => exact("**", a, b, integer_pow, rational_pow)
         }
     }
 
-    pub fn int_division(self, rhs: Value) -> Self {
+    pub fn int_division(self, rhs: Value) -> Result<Value, RuntimeError> {
+        if let Some((a, b)) = promote_bools(&self, &rhs) {
+            return a.int_division(b);
+        }
         match (self, rhs) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer((a / b) as i32),
-            (Value::Integer(a), Value::Float(b)) => Value::Float(((a as f64) / b) as i32 as f64),
-            (Value::Float(a), Value::Integer(b)) => Value::Float((a / (b as f64)) as i32 as f64),
-            (Value::Float(a), Value::Float(b)) => Value::Float((a / b) as i32 as f64),
-            _ => unreachable!()
+            (Value::Integer(_) | Value::BigInt(_) | Value::Rational(_), Value::Integer(0)) => Err(zero_division("integer division by zero")),
+            (Value::Float(_), Value::Integer(0)) => Err(zero_division("float floor division by zero")),
+            (Value::Integer(_) | Value::BigInt(_) | Value::Rational(_) | Value::Float(_), Value::Float(0.0)) => Err(zero_division("float floor division by zero")),
+            (Value::Integer(a), Value::Integer(b)) => Ok(floor_div(a, b).map_or_else(|| Value::from(BigInt::from(a).div_floor(&BigInt::from(b))), Value::Integer)),
+            (Value::Integer(a), Value::Float(b)) => Ok(Value::Float(float_floor_div(a as f64, b))),
+            (Value::Float(a), Value::Integer(b)) => Ok(Value::Float(float_floor_div(a, b as f64))),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(float_floor_div(a, b))),
+            (Value::BigInt(a), b @ Value::Float(_)) => Value::Float(big_to_float(&a)?).int_division(b),
+            (a @ Value::Float(_), Value::BigInt(b)) => a.int_division(Value::Float(big_to_float(&b)?)),
+            (Value::Rational(a), b @ Value::Float(_)) => Value::Float(rational_to_float(&a)?).int_division(b),
+            (a @ Value::Float(_), Value::Rational(b)) => a.int_division(Value::Float(rational_to_float(&b)?)),
+            // complex numbers have no floor, so they fall through to a type error
+            (a, b) => exact("//", a, b, |x, y| Ok(Value::from(x.div_floor(&y))), |x, y| {
+                if y.is_zero() {
+                    Err(zero_division("integer division by zero"))
+                } else {
+                    Ok(Value::from((x / y).floor().to_integer()))
+                }
+            })
+        }
+    }
+
+    /// The value as a real number for comparisons.
+    fn to_real(&self) -> Option<Real> {
+        match self {
+            Value::Float(x) => Some(Real::Float(*x)),
+            x => x.to_rational().map(Real::Exact),
+        }
+    }
+
+    /// The value as an exact fraction, if it is an integer or a rational.
+    pub fn to_rational(&self) -> Option<BigRational> {
+        match self {
+            Value::Rational(x) => Some(BigRational::clone(x)),
+            x => x.to_bigint().map(BigRational::from_integer),
+        }
+    }
+
+    /// The value as an arbitrary-precision integer, if it is an integer;
+    /// booleans count as 0 and 1.
+    pub fn to_bigint(&self) -> Option<BigInt> {
+        match self {
+            Value::Bool(x) => Some(BigInt::from(*x as i32)),
+            Value::Integer(x) => Some(BigInt::from(*x)),
+            Value::BigInt(x) => Some(BigInt::clone(x)),
+            _ => None,
+        }
+    }
+
+    /// Python's `repr`, which differs from `Display` by quoting strings.
+    pub fn repr(&self) -> String {
+        match self {
+            Value::Object(Object::String(x)) => {
+                let mut repr = String::from("'");
+                for chr in x.chars() {
+                    match chr {
+                        '\\' => repr.push_str("\\\\"),
+                        '\'' => repr.push_str("\\'"),
+                        '\n' => repr.push_str("\\n"),
+                        '\t' => repr.push_str("\\t"),
+                        '\r' => repr.push_str("\\r"),
+                        chr => repr.push(chr),
+                    }
+                }
+                repr.push('\'');
+                repr
+            }
+            value => value.to_string(),
+        }
+    }
+
+    /// Starts iterating over a builtin value for a `for` loop; the VM calls
+    /// `__iter__` for instances.
+    pub fn iter(&self) -> Result<Value, RuntimeError> {
+        let iterator = match self {
+            Value::Object(Object::List(x)) => ObjIterator::List { list: x.clone(), index: 0 },
+            Value::Object(Object::Range(x)) => ObjIterator::Range { range: x.clone(), index: 0 },
+            Value::Object(Object::String(x)) => ObjIterator::String { string: x.clone(), index: 0 },
+            Value::Object(Object::Iterator(_)) => return Ok(self.clone()),
+            x => return Err(RuntimeError::new(ErrorKind::Type, format!("'{}' object is not iterable", x.type_name()))),
+        };
+        Ok(Value::Object(Object::Iterator(Rc::new(RefCell::new(iterator)))))
+    }
+
+    /// Python's `item in self` for builtin containers; the VM handles
+    /// instances.
+    pub fn contains(&self, item: &Value) -> Result<bool, RuntimeError> {
+        match (self, item) {
+            (Value::Object(Object::String(x)), Value::Object(Object::String(y))) => Ok(x.contains(&**y)),
+            (Value::Object(Object::String(_)), y) => Err(RuntimeError::new(
+                ErrorKind::Type,
+                format!("'in <string>' requires string as left operand, not {}", y.type_name())
+            )),
+            (Value::Object(Object::List(x)), y) => Ok(x.borrow().iter().any(|x| x == y)),
+            (Value::Object(Object::Range(x)), y) => Ok(x.contains(y)),
+            (Value::Object(Object::Iterator(x)), y) => Ok(x.borrow_mut().any(|x| x == *y)),
+            (x, _) => Err(RuntimeError::new(ErrorKind::Type, format!("argument of type '{}' is not iterable", x.type_name()))),
+        }
+    }
+
+    /// Looks up `name` on the value: an instance's own fields first, then
+    /// its class and superclasses, binding methods to it.
+    pub fn get_attribute(&self, name: &Rc<str>) -> Result<Value, RuntimeError> {
+        match self {
+            Value::Object(Object::Instance(instance)) => {
+                if let Some(value) = instance.fields.borrow().get(name) {
+                    return Ok(value.clone());
+                }
+                match instance.class.find(name) {
+                    Some(value) => Ok(value.bind(self)),
+                    None => Err(no_attribute(self, name)),
+                }
+            }
+            Value::Object(Object::Class(class)) => class.find(name).ok_or_else(|| {
+                RuntimeError::new(ErrorKind::Attribute, format!("type object '{}' has no attribute '{}'", class.name, name))
+            }),
+            _ => Err(no_attribute(self, name)),
+        }
+    }
+
+    /// Binds a method found on the class of `receiver` to it; other class
+    /// attributes are returned as they are.
+    pub fn bind(self, receiver: &Value) -> Value {
+        match self {
+            Value::Object(Object::Closure(method)) => {
+                let bound = ObjBoundMethod { receiver: receiver.clone(), method };
+                Value::Object(Object::BoundMethod(Rc::new(bound)))
+            }
+            value => value,
+        }
+    }
+
+    /// Looks up a special method such as `__add__` on the class of an
+    /// instance, bound to it; instance fields are not consulted.
+    pub fn special_method(&self, name: &str) -> Option<Value> {
+        match self {
+            Value::Object(Object::Instance(instance)) => instance.class.find(name).map(|method| method.bind(self)),
+            _ => None,
+        }
+    }
+
+    /// Sets a field of an instance, or an attribute of a class.
+    pub fn set_attribute(&self, name: Rc<str>, value: Value) -> Result<(), RuntimeError> {
+        match self {
+            Value::Object(Object::Instance(instance)) => _ = instance.fields.borrow_mut().insert(name, value),
+            Value::Object(Object::Class(class)) => _ = class.attributes.borrow_mut().insert(name, value),
+            _ => return Err(no_attribute(self, &name)),
+        }
+        Ok(())
+    }
+
+    /// The name of the value's type in error messages; instances are named
+    /// after their class.
+    pub fn type_name(&self) -> Cow<'static, str> {
+        let name = match self {
+            Value::None => "NoneType",
+            Value::Bool(_) => "bool",
+            Value::Integer(_) | Value::BigInt(_) => "int",
+            Value::Rational(_) => "Fraction",
+            Value::Float(_) => "float",
+            Value::Complex(_) => "complex",
+            Value::Object(Object::Instance(x)) => return Cow::Owned(x.class.name.to_string()),
+            Value::Object(Object::Sentinel(x)) => x.type_name(),
+            Value::Object(x) => x.objecttype().into(),
+        };
+        Cow::Borrowed(name)
+    }
+
+}
+
+fn no_attribute(value: &Value, name: &str) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Attribute, format!("'{}' object has no attribute '{}'", value.type_name(), name))
+}
+
+/// Booleans take part in arithmetic as the integers 0 and 1. Gives the
+/// promoted operands when either is a boolean and both are numeric.
+fn promote_bools(a: &Value, b: &Value) -> Option<(Value, Value)> {
+    let promote = |x: &Value| match x {
+        Value::Bool(x) => Some(Value::Integer(*x as i32)),
+        x if is_number(x) => Some(x.clone()),
+        _ => None,
+    };
+    if !matches!((a, b), (Value::Bool(_), _) | (_, Value::Bool(_))) {
+        return None;
+    }
+    Some((promote(a)?, promote(b)?))
+}
+
+/// Integer division rounding towards negative infinity, as Python's `//` does.
+fn floor_div(a: i32, b: i32) -> Option<i32> {
+    let q = a.checked_div(b)?;
+    Some(if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q })
+}
+
+/// Floor division of floats, computed from the remainder like CPython so that
+/// `7.0 // 0.1` is `69.0` rather than the rounded `70.0`.
+fn float_floor_div(a: f64, b: f64) -> f64 {
+    let remainder = a % b;
+    let mut quotient = (a - remainder) / b;
+    if remainder != 0.0 && (b < 0.0) != (remainder < 0.0) {
+        quotient -= 1.0;
+    }
+
+    if quotient == 0.0 {
+        return 0.0_f64.copysign(a / b);
+    }
+    let floor = quotient.floor();
+    if quotient - floor > 0.5 { floor + 1.0 } else { floor }
+}
+
+/// Applies the exact form of an operator: `integers` when both operands are
+/// integers, otherwise `rationals` when both are at least rational.
+fn exact(
+    op: &str,
+    a: Value,
+    b: Value,
+    integers: fn(BigInt, BigInt) -> Result<Value, RuntimeError>,
+    rationals: fn(BigRational, BigRational) -> Result<Value, RuntimeError>,
+) -> Result<Value, RuntimeError> {
+    if let (Some(x), Some(y)) = (a.to_bigint(), b.to_bigint()) {
+        return integers(x, y);
+    }
+    match (a.to_rational(), b.to_rational()) {
+        (Some(x), Some(y)) => rationals(x, y),
+        _ => Err(unsupported(op, &a, &b)),
+    }
+}
+
+fn integer_pow(base: BigInt, exponent: BigInt) -> Result<Value, RuntimeError> {
+    if exponent.is_negative() {
+        if base.is_zero() {
+            return Err(zero_division("0.0 cannot be raised to a negative power"));
+        }
+        return float_pow(big_to_float(&base)?, big_to_float(&exponent)?);
+    }
+
+    match exponent.to_u32() {
+        Some(exponent) => Ok(Value::from(base.pow(exponent))),
+        // only 0, 1 and -1 stay small under an exponent this large
+        None if base.is_zero() || base == BigInt::from(1) => Ok(Value::from(base)),
+        None if base == BigInt::from(-1) => Ok(Value::Integer(if exponent.is_even() { 1 } else { -1 })),
+        None => Err(RuntimeError::new(ErrorKind::Overflow, "exponent too large")),
+    }
+}
+
+/// Rationals stay exact under integer powers and fall back to floats otherwise.
+fn rational_pow(base: BigRational, exponent: BigRational) -> Result<Value, RuntimeError> {
+    if !exponent.is_integer() {
+        return float_pow(rational_to_float(&base)?, rational_to_float(&exponent)?);
+    }
+
+    match exponent.to_integer().to_i32() {
+        Some(exponent) if exponent < 0 && base.is_zero() => Err(zero_division("division by zero")),
+        Some(exponent) => Ok(Value::from(Pow::pow(base, exponent))),
+        None => Err(RuntimeError::new(ErrorKind::Overflow, "exponent too large")),
+    }
+}
+
+fn rational_to_float(x: &BigRational) -> Result<f64, RuntimeError> {
+    match x.to_f64() {
+        Some(x) if x.is_finite() => Ok(x),
+        _ => Err(RuntimeError::new(ErrorKind::Overflow, "integer division result too large for a float")),
+    }
+}
+
+fn big_to_float(x: &BigInt) -> Result<f64, RuntimeError> {
+    match x.to_f64() {
+        Some(x) if x.is_finite() => Ok(x),
+        _ => Err(RuntimeError::new(ErrorKind::Overflow, "int too large to convert to float")),
+    }
+}
+
+/// A negative base to a fractional power has no real result, so like Python
+/// it becomes a complex one.
+fn float_pow(a: f64, b: f64) -> Result<Value, RuntimeError> {
+    if a == 0.0 && b < 0.0 {
+        Err(zero_division("0.0 cannot be raised to a negative power"))
+    } else if a < 0.0 && b.fract() != 0.0 && b.is_finite() {
+        complex_pow(Complex::new(a, 0.0), Complex::new(b, 0.0))
+    } else {
+        let result = a.powf(b);
+        if !result.is_finite() && a.is_finite() && b.is_finite() {
+            return Err(RuntimeError::new(ErrorKind::Overflow, "(34, 'Numerical result out of range')"));
+        }
+        Ok(Value::Float(result))
+    }
+}
+
+fn complex_pow(a: Complex<f64>, b: Complex<f64>) -> Result<Value, RuntimeError> {
+    if b == Complex::new(0.0, 0.0) {
+        return Ok(Value::Complex(Complex::new(1.0, 0.0)));
+    }
+    if a == Complex::new(0.0, 0.0) {
+        if b.im != 0.0 || b.re < 0.0 {
+            return Err(zero_division("0.0 to a negative or complex power"));
         }
+        return Ok(Value::Complex(a));
     }
+    Ok(Value::Complex(a.powc(b)))
+}
 
+fn unsupported(op: &str, a: &Value, b: &Value) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Type, format!("unsupported operand type(s) for {}: '{}' and '{}'", op, a.type_name(), b.type_name()))
+}
+
+fn bad_unary(op: &str, a: &Value) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Type, format!("bad operand type for unary {}: '{}'", op, a.type_name()))
+}
+
+fn zero_division(message: &str) -> RuntimeError {
+    RuntimeError::new(ErrorKind::ZeroDivision, message)
 }
 
 #[inline]
-pub fn is_number<'vm>(value: &'vm Value<'vm>) -> bool {
-    match value {
-        Value::Integer(_) | Value::Float(_) | Value::Complex(_) => true,
-        _ => false
+pub fn is_number(value: &Value) -> bool {
+    matches!(value, Value::Integer(_) | Value::BigInt(_) | Value::Rational(_) | Value::Float(_) | Value::Complex(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use num::{complex::Complex, BigInt, BigRational};
+
+    use crate::error::{ErrorKind, RuntimeError};
+    use super::Value;
+
+    #[test]
+    fn test_promotion() {
+        assert!(matches!(Value::Integer(1) + Value::Float(2.5), Ok(Value::Float(x)) if x == 3.5));
+        assert!(matches!(Value::Float(2.0) * Value::Complex(Complex::new(0.0, 1.0)), Ok(Value::Complex(x)) if x == Complex::new(0.0, 2.0)));
+        assert!(matches!(Value::Complex(Complex::new(1.0, 1.0)) - Value::Integer(1), Ok(Value::Complex(x)) if x == Complex::new(0.0, 1.0)));
+        assert!(matches!(Value::Integer(1) / Value::Integer(4), Ok(Value::Float(x)) if x == 0.25));
+        assert!(matches!(Value::Integer(2).pow(Value::Integer(10)), Ok(Value::Integer(1024))));
+        assert!(matches!(Value::Integer(2).pow(Value::Integer(-1)), Ok(Value::Float(x)) if x == 0.5));
+        assert!(matches!(Value::Integer(-8).pow(Value::Float(0.5)), Ok(Value::Complex(x)) if x.re.abs() < 1e-9 && (x.im - 8f64.sqrt()).abs() < 1e-9));
+        assert!(matches!(Value::Integer(-7).int_division(Value::Integer(2)), Ok(Value::Integer(-4))));
+        assert!(matches!(Value::Float(7.0).int_division(Value::Float(0.1)), Ok(Value::Float(x)) if x == 69.0));
+        assert!(matches!(Value::Integer(-1).int_division(Value::Float(3.0)), Ok(Value::Float(x)) if x == -1.0));
+    }
+
+    #[test]
+    fn test_booleans_are_integers() {
+        let text = |result: Result<Value, RuntimeError>| result.unwrap().to_string();
+        assert!(matches!(Value::Bool(true) + Value::Integer(1), Ok(Value::Integer(2))));
+        assert!(matches!(Value::Bool(true) + Value::Bool(true), Ok(Value::Integer(2))));
+        assert!(matches!(Value::Integer(1) - Value::Bool(true), Ok(Value::Integer(0))));
+        assert!(matches!(Value::Bool(true) * Value::Float(2.5), Ok(Value::Float(x)) if x == 2.5));
+        assert!(matches!(Value::Bool(false) / Value::Integer(2), Ok(Value::Float(x)) if x == 0.0));
+        assert!(matches!(Value::Integer(2).pow(Value::Bool(true)), Ok(Value::Integer(2))));
+        assert!(matches!(Value::Integer(7).int_division(Value::Bool(true)), Ok(Value::Integer(7))));
+        assert_eq!(text(Value::Bool(true) + Value::Complex(Complex::new(0.0, 1.0))), "(1+1j)");
+        assert_eq!(text(Value::string("ab") * Value::Bool(true)), "ab");
+        assert_eq!(text(Value::Bool(false) * Value::string("ab")), "");
+
+        let error = (Value::Integer(1) / Value::Bool(false)).unwrap_err();
+        assert_eq!((error.kind, error.message.as_str()), (ErrorKind::ZeroDivision, "division by zero"));
+        let error = (Value::Bool(true) + Value::string("a")).unwrap_err();
+        assert_eq!(error.message, "unsupported operand type(s) for +: 'bool' and 'str'");
+    }
+
+    #[test]
+    fn test_big_integers() {
+        let big = |text: &str| Value::from(text.parse::<BigInt>().unwrap());
+        let text = |result: Result<Value, RuntimeError>| result.unwrap().to_string();
+
+        assert_eq!(text(Value::Integer(i32::MAX) + Value::Integer(1)), "2147483648");
+        assert_eq!(text(Value::Integer(i32::MIN) - Value::Integer(1)), "-2147483649");
+        assert_eq!(text(Value::Integer(65536) * Value::Integer(65536)), "4294967296");
+        assert_eq!(text(Value::Integer(2).pow(Value::Integer(100))), "1267650600228229401496703205376");
+        assert_eq!(text(Value::Integer(i32::MIN).int_division(Value::Integer(-1))), "2147483648");
+        assert_eq!(text(big("-10000000000").int_division(Value::Integer(3))), "-3333333334");
+        assert!(matches!(big("4294967296") - big("4294967295"), Ok(Value::Integer(1))));
+        assert!(matches!(big("4294967296") + Value::Float(0.5), Ok(Value::Float(x)) if x == 4294967296.5));
+        assert!(matches!(big("4294967296") / big("2147483648"), Ok(Value::Float(x)) if x == 2.0));
+        assert!(matches!(big("4294967296") * Value::Complex(Complex::new(0.0, 1.0)), Ok(Value::Complex(x)) if x.im == 4294967296.0));
+        assert!(matches!(Value::Integer(-1).pow(big("4294967297")), Ok(Value::Integer(-1))));
+
+        let huge = Value::Integer(10).pow(Value::Integer(400)).unwrap();
+        assert!(matches!(huge.clone() / Value::Integer(10).pow(Value::Integer(399)).unwrap(), Ok(Value::Float(x)) if x == 10.0));
+        assert_eq!((huge.clone() + Value::Float(1.0)).unwrap_err().kind, ErrorKind::Overflow);
+        assert_eq!((huge / Value::Integer(0)).unwrap_err().kind, ErrorKind::ZeroDivision);
+    }
+
+    #[test]
+    fn test_rationals() {
+        let fraction = |n: i32, d: i32| Value::from(BigRational::new(n.into(), d.into()));
+        let text = |result: Result<Value, RuntimeError>| result.unwrap().to_string();
+
+        assert_eq!(text(fraction(1, 3) + fraction(1, 6)), "1/2");
+        assert_eq!(text(fraction(1, 10) * Value::Integer(3)), "3/10");
+        assert_eq!(text(Value::Integer(1) - fraction(1, 4)), "3/4");
+        assert_eq!(text(fraction(1, 2) / fraction(1, 4)), "2");
+        assert_eq!(text(fraction(2, 3).pow(Value::Integer(-2))), "9/4");
+        assert!(matches!(fraction(7, 2).int_division(fraction(1, 3)), Ok(Value::Integer(10))));
+        assert!(matches!(fraction(1, 4) + Value::Float(0.5), Ok(Value::Float(x)) if x == 0.75));
+        assert!(matches!(fraction(1, 4).pow(fraction(1, 2)), Ok(Value::Float(x)) if x == 0.5));
+        assert!(matches!(fraction(1, 2) * Value::Complex(Complex::new(0.0, 2.0)), Ok(Value::Complex(x)) if x == Complex::new(0.0, 1.0)));
+        assert_eq!((fraction(1, 2) / fraction(0, 1)).unwrap_err().kind, ErrorKind::ZeroDivision);
+        assert_eq!((fraction(1, 2) / Value::Integer(0)).unwrap_err().kind, ErrorKind::ZeroDivision);
+        assert_eq!(fraction(1, 2).type_name(), "Fraction");
+    }
+
+    #[test]
+    fn test_equality() {
+        let big = Value::Integer(2).pow(Value::Integer(53)).unwrap();
+        let fraction = Value::from(BigRational::new(1.into(), 2.into()));
+
+        assert_eq!(Value::Integer(1), Value::Float(1.0));
+        assert_eq!(Value::Bool(true), Value::Integer(1));
+        assert_eq!(Value::Bool(false), Value::Complex(Complex::new(0.0, 0.0)));
+        assert_eq!(Value::Complex(Complex::new(2.0, 0.0)), Value::Integer(2));
+        assert_ne!(Value::Complex(Complex::new(2.0, 1.0)), Value::Integer(2));
+        assert_eq!(fraction, Value::Float(0.5));
+        assert_eq!(big, Value::Float(9007199254740992.0));
+        assert_ne!((big + Value::Integer(1)).unwrap(), Value::Float(9007199254740992.0));
+        assert_ne!(Value::Float(f64::NAN), Value::Float(f64::NAN));
+        assert_ne!(Value::Integer(1), Value::string("1"));
+        assert_ne!(Value::None, Value::Bool(false));
+        assert_eq!(Value::None, Value::None);
+    }
+
+    #[test]
+    fn test_truthiness() {
+        let falsy = [Value::None, Value::Bool(false), Value::Integer(0), Value::Float(-0.0), Value::Complex(Complex::new(0.0, 0.0)), Value::string(""), Value::from(BigRational::from_integer(0.into()))];
+        let truthy = [Value::Bool(true), Value::Integer(-1), Value::Float(f64::NAN), Value::Complex(Complex::new(0.0, 1.0)), Value::string("0"), Value::from(BigRational::new(1.into(), 3.into()))];
+        assert!(falsy.iter().all(|x| !x.is_truthy()));
+        assert!(truthy.iter().all(|x| x.is_truthy()));
+    }
+
+    #[test]
+    fn test_ordering() {
+        let order = |a: Value, b: Value| a.compare(&b, "<").unwrap();
+        let huge = Value::Integer(10).pow(Value::Integer(400)).unwrap();
+
+        assert_eq!(order(Value::Integer(1), Value::Float(1.5)), Some(Ordering::Less));
+        assert_eq!(order(Value::Float(0.1), Value::from(BigRational::new(1.into(), 10.into()))), Some(Ordering::Greater));
+        assert_eq!(order(huge.clone(), Value::Float(f64::MAX)), Some(Ordering::Greater));
+        assert_eq!(order(huge, Value::Float(f64::INFINITY)), Some(Ordering::Less));
+        assert_eq!(order(Value::Bool(true), Value::Integer(0)), Some(Ordering::Greater));
+        assert_eq!(order(Value::Float(f64::NAN), Value::Integer(0)), None);
+
+        let error = Value::Complex(Complex::new(1.0, 0.0)).compare(&Value::Integer(1), "<").unwrap_err();
+        assert_eq!(error.message, "'<' not supported between instances of 'complex' and 'int'");
+        assert_eq!(Value::None.compare(&Value::None, ">=").unwrap_err().kind, ErrorKind::Type);
+    }
+
+    #[test]
+    fn test_numeric_errors() {
+        let kind = |result: Result<Value, RuntimeError>| result.unwrap_err().kind;
+        assert_eq!(kind(Value::Complex(Complex::new(1.0, 0.0)).int_division(Value::Integer(1))), ErrorKind::Type);
+        assert_eq!(kind(Value::Float(1.0).int_division(Value::Complex(Complex::new(1.0, 0.0)))), ErrorKind::Type);
+        assert_eq!(kind(Value::Complex(Complex::new(1.0, 0.0)) / Value::Float(0.0)), ErrorKind::ZeroDivision);
+        assert_eq!(kind(Value::Integer(1) / Value::Float(0.0)), ErrorKind::ZeroDivision);
+        assert_eq!(kind(Value::Integer(0).pow(Value::Integer(-1))), ErrorKind::ZeroDivision);
+        assert_eq!(kind(Value::Float(0.0).pow(Value::Complex(Complex::new(0.0, 1.0)))), ErrorKind::ZeroDivision);
+        assert_eq!(kind(Value::Float(10.0).pow(Value::Integer(400))), ErrorKind::Overflow);
+        assert!(matches!(Value::Float(f64::INFINITY).pow(Value::Integer(2)), Ok(Value::Float(x)) if x.is_infinite()));
     }
 }
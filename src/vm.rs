@@ -1,162 +1,1516 @@
+use std::{cell::RefCell, rc::Rc};
 
-use crate::{object::{ObjFucntion, Object}, chunk::{BytecodeOp, Op}, value::{Value, is_number}, token::Operator, compiler::Compiler};
+use crate::{object::{ObjClass, ObjClosure, ObjInstance, ObjIterator, ObjNative, ObjUpvalue, Object, Sentinel, intern}, native::builtins, chunk::{BytecodeOp, Op}, value::Value, token::Operator, compiler::{compile_with, CompileErrors}, error::{ErrorKind, RuntimeError, TraceFrame}};
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 
-#[derive(Debug, Clone, Copy)]
-enum InterpretResult {
-    CompileError,
-    RuntimeError,
-    Ok
+#[derive(Debug, Clone)]
+pub enum InterpretResult {
+    CompileError(CompileErrors),
+    RuntimeError(RuntimeError),
+    Ok(Value)
 }
 
-#[derive(Debug, Clone, Copy)]
-struct CallFrame<'vm> {
-    function: &'vm ObjFucntion<'vm>,
-    instructions: &'vm Vec<BytecodeOp>,
+/// What becomes of the value a special method returns, when the VM called
+/// it to finish an instruction.
+#[derive(Debug, Clone)]
+enum Continuation {
+    /// Pushed as it is, as after an ordinary call.
+    Push,
+    /// Its opposite truth value goes on, for `!=` between lists.
+    Not(Box<Continuation>),
+    /// From a binary operator's special method, which declines by returning
+    /// `NotImplemented`.
+    Operator(Box<Dispatch>),
+    /// From `__eq__` between elements of two lists being compared.
+    Elements(Box<Elements>),
+    /// Its truth value is pushed, for `in` answered by `__contains__`.
+    Truth,
+    /// Pushed if it is an iterator, for `__iter__`.
+    Iter,
+    /// From `__next__` for `ForIter`, where `StopIteration` ends the loop by
+    /// jumping `exit` ahead.
+    Next { exit: usize },
+    /// From `__iter__` for `in`, which goes on to search the iterator.
+    SearchIter { item: Value },
+    /// From `__next__` for `in`, searching `iterator` for `item`.
+    SearchNext { item: Value, iterator: Value },
+    /// From `__eq__` between `item` and a value from `iterator` for `in`.
+    SearchCompared { item: Value, iterator: Value },
+}
+
+/// A special method that may answer a binary operator, along with the other
+/// operand to pass it.
+#[derive(Debug, Clone)]
+struct Candidate {
+    method: Value,
+    argument: Value,
+    /// Set for `__eq__` standing in for a missing `__ne__`.
+    negate: bool,
+}
+
+/// A binary operator being answered by special methods, falling back to the
+/// builtin operator once all of them decline.
+#[derive(Debug, Clone)]
+struct Dispatch {
+    op: Operator,
+    a: Value,
+    b: Value,
+    /// The methods left to try, last first.
+    candidates: Vec<Candidate>,
+    /// Whether the method being run was a `Candidate` with `negate`.
+    negate: bool,
+    then: Continuation,
+}
+
+/// Two lists being compared for `==`, up to `index`.
+#[derive(Debug, Clone)]
+struct Elements {
+    a: Rc<RefCell<Vec<Value>>>,
+    b: Rc<RefCell<Vec<Value>>>,
+    index: usize,
+    then: Continuation,
+}
+
+#[derive(Debug, Clone)]
+struct CallFrame {
+    closure: Rc<ObjClosure>,
     instruction_index: usize,
-    slots: u8
+    slots: usize,
+    continuation: Continuation,
 }
 
-impl<'vm> CallFrame<'vm> {
+impl CallFrame {
     #[inline]
-    fn read_byte(self, frame_index: usize) -> (usize, BytecodeOp) {
-        (frame_index + 1, self.instructions[frame_index])
+    fn read_byte(&mut self) -> BytecodeOp {
+        let byte = self.closure.function.chunk.codes[self.instruction_index];
+        self.instruction_index += 1;
+        byte
     }
 
     #[inline]
-    fn read_constant(self, frame_index: usize) -> (usize, Value<'vm>) {
-        let constant_index: BytecodeOp;
-        (_, constant_index) = self.read_byte(frame_index);
-        let constant_index: usize = match constant_index {
+    fn read_constant(&mut self) -> Result<Value, RuntimeError> {
+        let constant_index: usize = match self.read_byte() {
             BytecodeOp::ConstantIndex(x) => x.into(),
-            _ => panic!("expected constant index")
+            byte => return Err(RuntimeError::new(ErrorKind::Internal, format!("expected constant index, got {:?}", byte)))
         };
-        let constant = self.function.chunk.constants[constant_index];
+        self.closure.function.chunk.constants.get(constant_index).cloned().ok_or_else(|| {
+            RuntimeError::new(ErrorKind::Internal, format!("constant index {} out of range", constant_index))
+        })
+    }
+
+    #[inline]
+    fn read_argument_count(&mut self) -> Result<u8, RuntimeError> {
+        match self.read_byte() {
+            BytecodeOp::ArgumentCount(x) => Ok(x),
+            byte => Err(RuntimeError::new(ErrorKind::Internal, format!("expected argument count, got {:?}", byte)))
+        }
+    }
+
+    #[inline]
+    fn read_element_count(&mut self) -> Result<usize, RuntimeError> {
+        match self.read_byte() {
+            BytecodeOp::ElementCount(x) => Ok(x.into()),
+            byte => Err(RuntimeError::new(ErrorKind::Internal, format!("expected element count, got {:?}", byte)))
+        }
+    }
 
-        (frame_index + 2, constant)
+    #[inline]
+    fn read_upvalue(&mut self) -> Result<Rc<RefCell<ObjUpvalue>>, RuntimeError> {
+        match self.read_byte() {
+            BytecodeOp::UpvalueIndex(x) => Ok(self.closure.upvalues[usize::from(x)].clone()),
+            byte => Err(RuntimeError::new(ErrorKind::Internal, format!("expected upvalue index, got {:?}", byte)))
+        }
+    }
+
+    #[inline]
+    fn read_jump(&mut self) -> Result<usize, RuntimeError> {
+        match self.read_byte() {
+            BytecodeOp::JumpDistance(x) => Ok(x.into()),
+            byte => Err(RuntimeError::new(ErrorKind::Internal, format!("expected jump distance, got {:?}", byte)))
+        }
+    }
+
+    #[inline]
+    fn read_slot(&mut self) -> Result<usize, RuntimeError> {
+        match self.read_byte() {
+            BytecodeOp::SlotIndex(x) => Ok(self.slots + usize::from(x)),
+            byte => Err(RuntimeError::new(ErrorKind::Internal, format!("expected slot index, got {:?}", byte)))
+        }
+    }
+
+    #[inline]
+    fn read_string(&mut self) -> Result<Rc<str>, RuntimeError> {
+        match self.read_constant()? {
+            Value::Object(Object::String(x)) => Ok(x),
+            constant => Err(RuntimeError::new(ErrorKind::Internal, format!("expected a name, got {}", constant)))
+        }
+    }
+
+    /// Source line of the instruction that was executing in this frame.
+    #[inline]
+    fn line(&self) -> usize {
+        let index = self.instruction_index.saturating_sub(1);
+        self.closure.function.chunk.lines.get(index).copied().unwrap_or_default().into()
     }
 }
 
+/// How deep calls may nest unless changed with `VM::frame_limit`.
+pub const DEFAULT_FRAME_LIMIT: usize = 1000;
+
 #[derive(Debug)]
-pub struct VM<'vm> {
-    stack: Vec<Value<'vm>>,
-    compilers: Vec<Compiler<'vm>>,
-    globals: HashMap<&'vm str, Value<'vm>>,
-    strings: HashMap<&'vm str, &'vm str>,
-    frames: Vec<CallFrame<'vm>>,
-    frame_count: usize,
-    objects: Vec<Object>,
+pub struct VM {
+    stack: Vec<Value>,
+    globals: HashMap<Rc<str>, Value>,
+    /// Names and string constants from compiled code; strings made while
+    /// running are not added, so they are freed when no longer used.
+    strings: HashSet<Rc<str>>,
+    frames: Vec<CallFrame>,
+    /// Upvalues still pointing into the stack, ordered by stack index.
+    open_upvalues: Vec<Rc<RefCell<ObjUpvalue>>>,
+    frame_limit: usize,
+    /// Name of the method that initializes new instances.
     init_string: &'static str,
 }
 
-impl<'vm> VM<'vm> {
-    pub fn new() -> VM<'vm> {
-        VM {
+impl Default for VM {
+    fn default() -> VM {
+        VM::new()
+    }
+}
+
+impl VM {
+    pub fn new() -> VM {
+        let mut vm = VM {
             stack: vec![],
-            compilers: vec![],
             globals: HashMap::new(),
-            strings: HashMap::new(),
+            strings: HashSet::new(),
             frames: vec![],
-            frame_count: 0,
-            objects: vec![],
-            init_string: "init"
+            open_upvalues: vec![],
+            frame_limit: DEFAULT_FRAME_LIMIT,
+            init_string: "__init__"
+        };
+
+        for native in builtins() {
+            let name = intern(&mut vm.strings, native.name);
+            vm.globals.insert(name, Value::Object(Object::Native(Rc::new(native))));
+        }
+        for sentinel in Sentinel::ALL {
+            let name = intern(&mut vm.strings, sentinel.name());
+            vm.globals.insert(name, Value::Object(Object::Sentinel(sentinel)));
         }
+        vm
     }
 
-    #[inline]
-    fn peek(&'vm self, distance: usize) -> &'vm Value {
-        self.stack.get(self.stack.len() - distance).unwrap()
+    /// Sets how many calls may be active at once, counting the script itself;
+    /// going deeper raises a `RecursionError`.
+    pub fn frame_limit(mut self, limit: usize) -> VM {
+        self.frame_limit = limit;
+        self
     }
 
-    #[inline]
-    fn binary_op(&mut self, op: Operator) {
-        if !is_number(self.peek(0)) || !is_number(self.peek(1)) {
-            panic!("both operands must be numbers")
+    /// Compiles `source` as a script and runs it, producing the value of the
+    /// script's final expression statement (or `None`).
+    pub fn interpret(&mut self, source: &str) -> InterpretResult {
+        let function = match compile_with(source, &mut self.strings) {
+            Ok(function) => Rc::new(function),
+            Err(errors) => return InterpretResult::CompileError(errors),
+        };
+
+        let closure = Rc::new(ObjClosure { function, upvalues: vec![], class: None });
+        self.stack.push(Value::Object(Object::Closure(closure.clone())));
+        let result = self.call(closure, 0).and_then(|_| self.run());
+
+        match result {
+            Ok(value) => InterpretResult::Ok(value),
+            Err(mut error) => {
+                error.traceback = self.traceback();
+                self.stack.clear();
+                self.frames.clear();
+                self.open_upvalues.clear();
+                InterpretResult::RuntimeError(error)
+            }
         }
+    }
+
+    fn traceback(&self) -> Vec<TraceFrame> {
+        self.frames.iter().map(|frame| TraceFrame { function: frame.closure.function.name.clone(), line: frame.line() + 1 }).collect()
+    }
 
+    #[inline]
+    fn binary_op(&mut self, op: Operator) -> Result<(), RuntimeError> {
         let b: Value = self.stack.pop().unwrap();
         let a: Value = self.stack.pop().unwrap();
-        let c: Value = op.doit(a, b);
+        let candidates = binary_candidates(op, &a, &b);
+        if !candidates.is_empty() {
+            return self.dispatch(Box::new(Dispatch { op, a, b, candidates, negate: false, then: Continuation::Push }));
+        }
+
+        // lists compare their elements with `==`, which may call `__eq__`
+        if let (Value::Object(Object::List(x)), Value::Object(Object::List(y)), Operator::EqualEqual | Operator::NotEqual) = (&a, &b, op) {
+            let negate = op == Operator::NotEqual;
+            let then = if negate { Continuation::Not(Box::new(Continuation::Push)) } else { Continuation::Push };
+            if let Some(equal) = self.elements(x, y, 0, &then)? {
+                self.stack.push(Value::Bool(equal != negate));
+            }
+            return Ok(());
+        }
+
+        let c: Value = op.doit(a, b)?;
         self.stack.push(c);
-        
+        Ok(())
     }
 
-    fn call(&'vm mut self, function: &'vm ObjFucntion<'vm>, num_args: u8) -> bool {
-        _ = if num_args != function.arity {
-            panic!("wrong number of arguments, got {} expected {}", num_args, function.arity);
-        };
+    /// Runs the next special method for a binary operator, or the builtin
+    /// operator once every method has declined.
+    fn dispatch(&mut self, mut dispatch: Box<Dispatch>) -> Result<(), RuntimeError> {
+        match dispatch.candidates.pop() {
+            Some(Candidate { method, argument, negate }) => {
+                dispatch.negate = negate;
+                self.call_special(method, &[argument], Continuation::Operator(dispatch))
+            }
+            None => {
+                let Dispatch { op, a, b, then, .. } = *dispatch;
+                let result = op.doit(a, b)?;
+                self.resume(then, result)
+            }
+        }
+    }
 
-        _ = if self.frames.len() == u8::MAX.into() {
-            panic!("stack overflow")
-        };
+    /// Works out `a == b` for comparing the contents of containers, through
+    /// `__eq__` for instances. Gives the answer if no special method had to
+    /// be called; otherwise the answer goes to `then` once the method returns.
+    fn equal(&mut self, a: &Value, b: &Value, then: impl FnOnce() -> Continuation) -> Result<Option<bool>, RuntimeError> {
+        if let (Value::Object(Object::Instance(x)), Value::Object(Object::Instance(y))) = (a, b) {
+            // containers check identity first, like Python
+            if Rc::ptr_eq(x, y) {
+                return Ok(Some(true));
+            }
+        }
 
-        let frame: CallFrame = CallFrame { function: &function, instructions: &function.chunk.codes, instruction_index: 0, slots: self.stack.len() as u8 - num_args - 1 };
-        self.frames.insert(self.frame_count, frame);
-        self.frame_count += 1;
+        let candidates = binary_candidates(Operator::EqualEqual, a, b);
+        if !candidates.is_empty() {
+            let dispatch = Dispatch { op: Operator::EqualEqual, a: a.clone(), b: b.clone(), candidates, negate: false, then: then() };
+            self.dispatch(Box::new(dispatch))?;
+            return Ok(None);
+        }
+        match (a, b) {
+            (Value::Object(Object::List(x)), Value::Object(Object::List(y))) => self.elements(x, y, 0, &then()),
+            _ => Ok(Some(a == b)),
+        }
+    }
 
-        true
+    /// Compares two lists element by element from `index`, in the manner of
+    /// `equal`.
+    fn elements(&mut self, a: &Rc<RefCell<Vec<Value>>>, b: &Rc<RefCell<Vec<Value>>>, mut index: usize, then: &Continuation) -> Result<Option<bool>, RuntimeError> {
+        if Rc::ptr_eq(a, b) {
+            return Ok(Some(true));
+        }
+        if index == 0 && a.borrow().len() != b.borrow().len() {
+            return Ok(Some(false));
+        }
+        // `__eq__` may change either list, so they are checked again each time
+        loop {
+            let (x, y) = match (a.borrow().get(index).cloned(), b.borrow().get(index).cloned()) {
+                (Some(x), Some(y)) => (x, y),
+                (None, None) => return Ok(Some(true)),
+                _ => return Ok(Some(false)),
+            };
+
+            index += 1;
+            let rest = || Continuation::Elements(Box::new(Elements { a: a.clone(), b: b.clone(), index, then: then.clone() }));
+            match self.equal(&x, &y, rest)? {
+                Some(true) => continue,
+                answer => return Ok(answer),
+            }
+        }
     }
 
+    /// Applies a unary operator through the operand's special method `name`
+    /// when its class defines one.
+    fn unary_op(&mut self, name: &str, value: Value, builtin: fn(Value) -> Result<Value, RuntimeError>) -> Result<(), RuntimeError> {
+        match value.special_method(name) {
+            Some(method) => self.call_special(method, &[], Continuation::Push),
+            None => {
+                self.stack.push(builtin(value)?);
+                Ok(())
+            }
+        }
+    }
 
-    fn run(&'vm mut self) -> InterpretResult {
-        let frame: CallFrame = self.frames.get(self.frame_count - 1).cloned().unwrap();
-        let mut frame_index: usize = 0;
-        let mut instruction: BytecodeOp;
+    /// Calls a special method to finish the current instruction. Its result
+    /// goes to `continuation` once it returns, rather than the VM running it
+    /// to completion here.
+    fn call_special(&mut self, method: Value, arguments: &[Value], continuation: Continuation) -> Result<(), RuntimeError> {
+        let depth = self.frames.len();
+        self.stack.push(method);
+        self.stack.extend_from_slice(arguments);
+        self.call_value(arguments.len() as u8)?;
 
+        // natives and classes without `__init__` have already returned
+        if self.frames.len() > depth {
+            self.frames.last_mut().unwrap().continuation = continuation;
+            Ok(())
+        } else {
+            let result = self.stack.pop().unwrap();
+            self.resume(continuation, result)
+        }
+    }
+
+    /// Finishes the instruction that called a special method with the value
+    /// the method returned.
+    fn resume(&mut self, continuation: Continuation, result: Value) -> Result<(), RuntimeError> {
+        match continuation {
+            Continuation::Push => self.stack.push(result),
+            Continuation::Not(then) => return self.resume(*then, Value::Bool(!result.is_truthy())),
+            Continuation::Operator(dispatch) if is_not_implemented(&result) => return self.dispatch(dispatch),
+            Continuation::Operator(dispatch) => {
+                let result = if dispatch.negate { Value::Bool(!result.is_truthy()) } else { result };
+                return self.resume(dispatch.then, result);
+            }
+            Continuation::Elements(elements) if !result.is_truthy() => return self.resume(elements.then, Value::Bool(false)),
+            Continuation::Elements(elements) => {
+                let Elements { a, b, index, then } = *elements;
+                if let Some(equal) = self.elements(&a, &b, index, &then)? {
+                    return self.resume(then, Value::Bool(equal));
+                }
+            }
+            Continuation::Truth => self.stack.push(Value::Bool(result.is_truthy())),
+            Continuation::Iter => self.stack.push(checked_iterator(result)?),
+            Continuation::Next { exit } if is_stop(&result) => self.frames.last_mut().unwrap().instruction_index += exit,
+            Continuation::Next { .. } => self.stack.push(result),
+            Continuation::SearchIter { item } => return self.search(item, checked_iterator(result)?, None),
+            Continuation::SearchNext { .. } if is_stop(&result) => self.stack.push(Value::Bool(false)),
+            Continuation::SearchNext { item, iterator } => return self.search(item, iterator, Some(result)),
+            Continuation::SearchCompared { .. } if result.is_truthy() => self.stack.push(Value::Bool(true)),
+            Continuation::SearchCompared { item, iterator } => return self.search(item, iterator, None),
+        }
+        Ok(())
+    }
+
+    /// Starts iterating over `iterable` for a `for` loop, through `__iter__`
+    /// for instances.
+    fn get_iter(&mut self, iterable: Value) -> Result<(), RuntimeError> {
+        match iterable.special_method("__iter__") {
+            Some(method) => self.call_special(method, &[], Continuation::Iter),
+            None => {
+                self.stack.push(iterable.iter()?);
+                Ok(())
+            }
+        }
+    }
+
+    /// `item in container`, through `__contains__` for instances, or else by
+    /// searching what their `__iter__` produces.
+    fn contains(&mut self, item: Value, container: Value) -> Result<(), RuntimeError> {
+        if let Some(method) = container.special_method("__contains__") {
+            return self.call_special(method, &[item], Continuation::Truth);
+        }
+        if let Some(method) = container.special_method("__iter__") {
+            return self.call_special(method, &[], Continuation::SearchIter { item });
+        }
+        match container {
+            Value::Object(Object::List(list)) => {
+                let iterator = ObjIterator::List { list, index: 0 };
+                self.search(item, Value::Object(Object::Iterator(Rc::new(RefCell::new(iterator)))), None)
+            }
+            Value::Object(Object::Iterator(_)) => self.search(item, container, None),
+            container => {
+                self.stack.push(Value::Bool(container.contains(&item)?));
+                Ok(())
+            }
+        }
+    }
+
+    /// Pushes whether `iterator` produces a value equal to `item`, calling
+    /// `__next__` until it gives `StopIteration` for instances. `element` is
+    /// a value already taken from it.
+    fn search(&mut self, item: Value, iterator: Value, mut element: Option<Value>) -> Result<(), RuntimeError> {
         loop {
-            (frame_index, instruction) = frame.read_byte(frame_index);
+            let element = match element.take() {
+                Some(element) => element,
+                None => match &iterator {
+                    Value::Object(Object::Iterator(builtin)) => match builtin.borrow_mut().next() {
+                        Some(element) => element,
+                        None => break,
+                    },
+                    _ => match iterator.special_method("__next__") {
+                        Some(method) => return self.call_special(method, &[], Continuation::SearchNext { item, iterator }),
+                        None => return Err(not_an_iterator(&iterator)),
+                    },
+                },
+            };
+
+            // Python compares each element with the item, not the other way
+            let rest = || Continuation::SearchCompared { item: item.clone(), iterator: iterator.clone() };
+            match self.equal(&element, &item, rest)? {
+                Some(true) => {
+                    self.stack.push(Value::Bool(true));
+                    return Ok(());
+                }
+                Some(false) => continue,
+                None => return Ok(()),
+            }
+        }
+        self.stack.push(Value::Bool(false));
+        Ok(())
+    }
+
+    fn call_value(&mut self, num_args: u8) -> Result<(), RuntimeError> {
+        let callee = self.stack[self.stack.len() - num_args as usize - 1].clone();
+        match callee {
+            Value::Object(Object::Closure(closure)) => self.call(closure, num_args),
+            Value::Object(Object::BoundMethod(bound)) => {
+                // the receiver becomes the first argument
+                let callee = self.stack.len() - num_args as usize - 1;
+                self.stack.insert(callee + 1, bound.receiver.clone());
+                self.call(bound.method.clone(), with_receiver(num_args)?)
+            }
+            Value::Object(Object::Class(class)) => {
+                // the instance takes the place of the class, where `__init__`
+                // returns it from
+                let callee = self.stack.len() - num_args as usize - 1;
+                let instance = Value::Object(Object::Instance(Rc::new(ObjInstance::new(class.clone()))));
+                self.stack[callee] = instance.clone();
+
+                let initializer = class.find(self.init_string);
+                match initializer {
+                    Some(Value::Object(Object::Closure(initializer))) => {
+                        self.stack.insert(callee + 1, instance);
+                        self.call(initializer, with_receiver(num_args)?)
+                    }
+                    Some(initializer) => Err(RuntimeError::new(ErrorKind::Type, format!("'{}' object is not callable", initializer.type_name()))),
+                    None if num_args == 0 => Ok(()),
+                    None => Err(RuntimeError::new(ErrorKind::Type, format!("{}() takes no arguments", class.name))),
+                }
+            }
+            Value::Object(Object::Native(native)) => self.call_native(native, num_args),
+            callee => Err(RuntimeError::new(ErrorKind::Type, format!("'{}' object is not callable", callee.type_name()))),
+        }
+    }
+
+    fn call_native(&mut self, native: Rc<ObjNative>, num_args: u8) -> Result<(), RuntimeError> {
+        let min_arity = native.arity - native.optional;
+        if num_args > native.arity || num_args < min_arity {
+            let expected = if native.optional == 0 {
+                native.arity.to_string()
+            } else {
+                format!("from {} to {}", min_arity, native.arity)
+            };
+            return Err(RuntimeError::new(
+                ErrorKind::Type,
+                format!("{}() takes {} positional arguments but {} were given", native.name, expected, num_args)
+            ));
+        }
+
+        let args_start = self.stack.len() - num_args as usize;
+        let result = (native.function)(&self.stack[args_start..])?;
+        self.stack.truncate(args_start - 1);
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn call(&mut self, closure: Rc<ObjClosure>, num_args: u8) -> Result<(), RuntimeError> {
+        let function = &closure.function;
+        if num_args != function.arity {
+            return Err(RuntimeError::new(
+                ErrorKind::Type,
+                format!("{}() takes {} positional arguments but {} were given", function.name, function.arity, num_args)
+            ));
+        }
+
+        if self.frames.len() >= self.frame_limit {
+            return Err(RuntimeError::new(ErrorKind::Recursion, "maximum recursion depth exceeded"));
+        }
+
+        let slots = self.stack.len() - num_args as usize - 1;
+        self.frames.push(CallFrame { closure, instruction_index: 0, slots, continuation: Continuation::Push });
+
+        Ok(())
+    }
+
+    /// Moves every open upvalue at or above stack index `last` off the stack.
+    fn close_upvalues(&mut self, last: usize) {
+        while let Some(upvalue) = self.open_upvalues.last() {
+            let slot = match *upvalue.borrow() {
+                ObjUpvalue::Open(slot) if slot >= last => slot,
+                _ => break,
+            };
+            *upvalue.borrow_mut() = ObjUpvalue::Closed(self.stack[slot].clone());
+            self.open_upvalues.pop();
+        }
+    }
+
+
+    fn run(&mut self) -> Result<Value, RuntimeError> {
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let instruction = frame.read_byte();
             match instruction {
                 BytecodeOp::Op(Op::Constant) => {
-                    let constant: Value;
-                    (frame_index, constant) = frame.read_constant(frame_index);
+                    let constant = frame.read_constant()?;
                     self.stack.push(constant);
                 }
                 BytecodeOp::Op(Op::True) => self.stack.push(Value::Bool(true)),
                 BytecodeOp::Op(Op::False) => self.stack.push(Value::Bool(false)),
+                BytecodeOp::Op(Op::None) => self.stack.push(Value::None),
                 BytecodeOp::Op(Op::Pop) => _ = self.stack.pop(),
-                BytecodeOp::Op(Op::ValueEqual) => self.binary_op(Operator::EqualEqual),
-                BytecodeOp::Op(Op::NotValueEqual) => self.binary_op(Operator::NotEqual),
-                BytecodeOp::Op(Op::Less) => self.binary_op(Operator::Less),
-                BytecodeOp::Op(Op::LessEqual) => self.binary_op(Operator::LessEqual),
-                BytecodeOp::Op(Op::Greater) => self.binary_op(Operator::Greater),
-                BytecodeOp::Op(Op::GreaterEqual) => self.binary_op(Operator::GreaterEqual),
-                BytecodeOp::Op(Op::Add) => self.binary_op(Operator::Plus),
-                BytecodeOp::Op(Op::Subtract) => self.binary_op(Operator::Minus),
-                BytecodeOp::Op(Op::Multiply) => self.binary_op(Operator::Star),
-                BytecodeOp::Op(Op::Exponent) => self.binary_op(Operator::StarStar),
-                BytecodeOp::Op(Op::Divide) => self.binary_op(Operator::Slash),
-                BytecodeOp::Op(Op::IntDivide) => self.binary_op(Operator::SlashSlash),
-                BytecodeOp::Op(Op::Return) => {
-                    self.frame_count -= 1;
-
-                    if self.frame_count == 0 {
-                        self.stack.pop(); // result
+                BytecodeOp::Op(Op::DefineGlobal) => {
+                    let name = frame.read_string()?;
+                    let value = self.stack.pop().unwrap();
+                    self.globals.insert(name, value);
+                }
+                BytecodeOp::Op(Op::GetGlobal) => {
+                    let name = frame.read_string()?;
+                    match self.globals.get(&name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(RuntimeError::new(ErrorKind::Name, format!("name '{}' is not defined", name))),
+                    }
+                }
+                BytecodeOp::Op(Op::SetGlobal) => {
+                    let name = frame.read_string()?;
+                    let value = self.stack.last().unwrap().clone();
+                    self.globals.insert(name, value);
+                }
+                BytecodeOp::Op(Op::GetLocal) => {
+                    let slot = frame.read_slot()?;
+                    self.stack.push(self.stack[slot].clone());
+                }
+                BytecodeOp::Op(Op::SetLocal) => {
+                    let slot = frame.read_slot()?;
+                    self.stack[slot] = self.stack.last().unwrap().clone();
+                }
+                BytecodeOp::Op(Op::Dup) => self.stack.push(self.stack.last().unwrap().clone()),
+                BytecodeOp::Op(Op::Swap) => {
+                    let len = self.stack.len();
+                    self.stack.swap(len - 1, len - 2);
+                }
+                BytecodeOp::Op(Op::Rotate) => {
+                    let top = self.stack.pop().unwrap();
+                    self.stack.insert(self.stack.len() - 2, top);
+                }
+                BytecodeOp::Op(Op::GetUpvalue) => {
+                    let value = match &*frame.read_upvalue()?.borrow() {
+                        ObjUpvalue::Open(slot) => self.stack[*slot].clone(),
+                        ObjUpvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                BytecodeOp::Op(Op::SetUpvalue) => {
+                    let value = self.stack.last().unwrap().clone();
+                    match &mut *frame.read_upvalue()?.borrow_mut() {
+                        ObjUpvalue::Open(slot) => self.stack[*slot] = value,
+                        ObjUpvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                BytecodeOp::Op(Op::CloseUpvalue) => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.stack.pop();
+                }
+                BytecodeOp::Op(Op::Jump) => {
+                    let distance = frame.read_jump()?;
+                    frame.instruction_index += distance;
+                }
+                BytecodeOp::Op(Op::Loop) => {
+                    let distance = frame.read_jump()?;
+                    frame.instruction_index -= distance;
+                }
+                BytecodeOp::Op(Op::JumpIfFalse) => {
+                    let distance = frame.read_jump()?;
+                    if !self.stack.pop().unwrap().is_truthy() {
+                        frame.instruction_index += distance;
+                    }
+                }
+                BytecodeOp::Op(Op::JumpIfFalseOrPop) => {
+                    let distance = frame.read_jump()?;
+                    if self.stack.last().unwrap().is_truthy() {
                         self.stack.pop();
-                        return InterpretResult::Ok;
+                    } else {
+                        frame.instruction_index += distance;
                     }
+                }
+                BytecodeOp::Op(Op::JumpIfTrueOrPop) => {
+                    let distance = frame.read_jump()?;
+                    if self.stack.last().unwrap().is_truthy() {
+                        frame.instruction_index += distance;
+                    } else {
+                        self.stack.pop();
+                    }
+                }
+                BytecodeOp::Op(Op::Negative) => {
+                    let value = self.stack.pop().unwrap();
+                    self.unary_op("__neg__", value, |value| -value)?;
+                }
+                BytecodeOp::Op(Op::Positive) => {
+                    let value = self.stack.pop().unwrap();
+                    self.unary_op("__pos__", value, Value::positive)?;
+                }
+                BytecodeOp::Op(Op::Not) => {
+                    let value = self.stack.pop().unwrap();
+                    self.stack.push(Value::Bool(!value.is_truthy()));
+                }
+                BytecodeOp::Op(Op::ValueEqual) => self.binary_op(Operator::EqualEqual)?,
+                BytecodeOp::Op(Op::NotValueEqual) => self.binary_op(Operator::NotEqual)?,
+                BytecodeOp::Op(Op::Less) => self.binary_op(Operator::Less)?,
+                BytecodeOp::Op(Op::LessEqual) => self.binary_op(Operator::LessEqual)?,
+                BytecodeOp::Op(Op::Greater) => self.binary_op(Operator::Greater)?,
+                BytecodeOp::Op(Op::GreaterEqual) => self.binary_op(Operator::GreaterEqual)?,
+                BytecodeOp::Op(Op::Add) => self.binary_op(Operator::Plus)?,
+                BytecodeOp::Op(Op::Subtract) => self.binary_op(Operator::Minus)?,
+                BytecodeOp::Op(Op::Multiply) => self.binary_op(Operator::Star)?,
+                BytecodeOp::Op(Op::Exponent) => self.binary_op(Operator::StarStar)?,
+                BytecodeOp::Op(Op::Divide) => self.binary_op(Operator::Slash)?,
+                BytecodeOp::Op(Op::IntDivide) => self.binary_op(Operator::SlashSlash)?,
+                BytecodeOp::Op(Op::BuildList) => {
+                    let count = frame.read_element_count()?;
+                    let items = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Value::Object(Object::List(Rc::new(RefCell::new(items)))));
+                }
+                BytecodeOp::Op(Op::GetIter) => {
+                    let iterable = self.stack.pop().unwrap();
+                    self.get_iter(iterable)?;
+                }
+                BytecodeOp::Op(Op::ForIter) => {
+                    let distance = frame.read_jump()?;
+                    let iterator = self.stack.last().unwrap();
+                    if let Value::Object(Object::Iterator(iterator)) = iterator {
+                        let next = iterator.borrow_mut().next();
+                        match next {
+                            Some(value) => self.stack.push(value),
+                            None => frame.instruction_index += distance,
+                        }
+                    } else {
+                        match iterator.special_method("__next__") {
+                            Some(method) => self.call_special(method, &[], Continuation::Next { exit: distance })?,
+                            None => return Err(not_an_iterator(iterator)),
+                        }
+                    }
+                }
+                BytecodeOp::Op(Op::Contains) => {
+                    let container = self.stack.pop().unwrap();
+                    let item = self.stack.pop().unwrap();
+                    self.contains(item, container)?;
+                }
+                BytecodeOp::Op(Op::Class) => {
+                    let name = frame.read_string()?;
+                    self.stack.push(Value::Object(Object::Class(Rc::new(ObjClass::new(name, None)))));
+                }
+                BytecodeOp::Op(Op::Subclass) => {
+                    let name = frame.read_string()?;
+                    let superclass = match self.stack.pop().unwrap() {
+                        Value::Object(Object::Class(superclass)) => superclass,
+                        value => return Err(RuntimeError::new(ErrorKind::Type, format!("cannot inherit from '{}' object", value.type_name()))),
+                    };
+                    self.stack.push(Value::Object(Object::Class(Rc::new(ObjClass::new(name, Some(superclass))))));
+                }
+                BytecodeOp::Op(Op::Method) => {
+                    let name = frame.read_string()?;
+                    let value = match self.stack.pop().unwrap() {
+                        // methods remember their class for `super()`
+                        Value::Object(Object::Closure(method)) => {
+                            let class = match self.stack.last() {
+                                Some(Value::Object(Object::Class(class))) => class.clone(),
+                                top => return Err(RuntimeError::new(ErrorKind::Internal, format!("expected a class, got {:?}", top))),
+                            };
+                            let method = ObjClosure { function: method.function.clone(), upvalues: method.upvalues.clone(), class: Some(class) };
+                            Value::Object(Object::Closure(Rc::new(method)))
+                        }
+                        value => value,
+                    };
+                    self.stack.last().unwrap().set_attribute(name, value)?;
+                }
+                BytecodeOp::Op(Op::GetSuper) => {
+                    let name = frame.read_string()?;
+                    let superclass = match &frame.closure.class {
+                        Some(class) => class.superclass.clone(),
+                        None => return Err(RuntimeError::new(ErrorKind::Internal, "super() outside of a method")),
+                    };
+                    let receiver = self.stack.pop().unwrap();
+                    match superclass.and_then(|superclass| superclass.find(&name)) {
+                        Some(value) => self.stack.push(value.bind(&receiver)),
+                        None => return Err(RuntimeError::new(ErrorKind::Attribute, format!("'super' object has no attribute '{}'", name))),
+                    }
+                }
+                BytecodeOp::Op(Op::GetAttribute) => {
+                    let name = frame.read_string()?;
+                    let value = self.stack.pop().unwrap().get_attribute(&name)?;
+                    self.stack.push(value);
+                }
+                BytecodeOp::Op(Op::SetAttribute) => {
+                    let name = frame.read_string()?;
+                    let value = self.stack.pop().unwrap();
+                    let object = self.stack.pop().unwrap();
+                    object.set_attribute(name, value.clone())?;
+                    self.stack.push(value);
+                }
+                BytecodeOp::Op(Op::Closure) => {
+                    let function = match frame.read_constant()? {
+                        Value::Object(Object::Function(function)) => function,
+                        constant => return Err(RuntimeError::new(ErrorKind::Internal, format!("expected a function, got {}", constant))),
+                    };
+
+                    let mut upvalues = Vec::with_capacity(function.upvalue_count.into());
+                    for _ in 0..function.upvalue_count {
+                        let upvalue = match frame.read_byte() {
+                            BytecodeOp::Capture { local: true, index } => capture_upvalue(&mut self.open_upvalues, frame.slots + usize::from(index)),
+                            BytecodeOp::Capture { local: false, index } => frame.closure.upvalues[usize::from(index)].clone(),
+                            byte => return Err(RuntimeError::new(ErrorKind::Internal, format!("expected a capture, got {:?}", byte))),
+                        };
+                        upvalues.push(upvalue);
+                    }
+                    self.stack.push(Value::Object(Object::Closure(Rc::new(ObjClosure { function, upvalues, class: None }))));
+                }
+                BytecodeOp::Op(Op::Call) => {
+                    let num_args = frame.read_argument_count()?;
+                    self.call_value(num_args)?;
+                }
+                BytecodeOp::Op(Op::Assert) => {
+                    if !self.stack.pop().unwrap().is_truthy() {
+                        return Err(RuntimeError::new(ErrorKind::Assertion, ""));
+                    }
+                }
+                BytecodeOp::Op(Op::Return) => {
+                    let result = self.stack.pop().unwrap();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
 
-                    _ = self.frames.pop();
+                    if self.frames.is_empty() {
+                        return Ok(result);
+                    }
 
+                    self.resume(frame.continuation, result)?;
                 }
-                _ => panic!("got jump or constant index")
+                byte => return Err(RuntimeError::new(ErrorKind::Internal, format!("cannot execute {:?}", byte)))
             }
-                
+
         }
 
     }
 
 }
 
+/// The special methods that may answer `a op b`, last first: the left
+/// operand's, then the right operand's reflected one. Without `__ne__`, `!=`
+/// is the opposite of `__eq__`.
+fn binary_candidates(op: Operator, a: &Value, b: &Value) -> Vec<Candidate> {
+    let class = |value: &Value| match value {
+        Value::Object(Object::Instance(instance)) => Some(instance.class.clone()),
+        _ => None,
+    };
+    let (Some((name, reflected)), left, right) = (op.dunder(), class(a), class(b)) else {
+        return vec![];
+    };
+    if left.is_none() && right.is_none() {
+        return vec![];
+    }
+
+    let candidate = |receiver: &Value, name: &str, argument: &Value| {
+        let found = match receiver.special_method(name) {
+            Some(method) => Some((method, false)),
+            None if op == Operator::NotEqual => receiver.special_method("__eq__").map(|method| (method, true)),
+            None => None,
+        };
+        found.map(|(method, negate)| Candidate { method, argument: argument.clone(), negate })
+    };
+
+    let mut candidates = vec![];
+    candidates.extend(candidate(a, name, b));
+    // arithmetic between instances of one class never tries the reflected method
+    let comparison = matches!(op, Operator::EqualEqual | Operator::NotEqual | Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual);
+    let same_class = matches!((&left, &right), (Some(x), Some(y)) if Rc::ptr_eq(x, y));
+    if comparison || !same_class {
+        candidates.extend(candidate(b, reflected, a));
+    }
+    candidates.reverse();
+    candidates
+}
+
+/// Checks that `__iter__` gave something `ForIter` can take values from.
+fn checked_iterator(value: Value) -> Result<Value, RuntimeError> {
+    if matches!(value, Value::Object(Object::Iterator(_))) || value.special_method("__next__").is_some() {
+        Ok(value)
+    } else {
+        Err(RuntimeError::new(ErrorKind::Type, format!("iter() returned non-iterator of type '{}'", value.type_name())))
+    }
+}
+
+fn is_stop(value: &Value) -> bool {
+    matches!(value, Value::Object(Object::Sentinel(Sentinel::StopIteration)))
+}
+
+fn is_not_implemented(value: &Value) -> bool {
+    matches!(value, Value::Object(Object::Sentinel(Sentinel::NotImplemented)))
+}
+
+fn not_an_iterator(value: &Value) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Type, format!("'{}' object is not an iterator", value.type_name()))
+}
+
+/// The argument count of a call once the receiver is passed as an argument.
+fn with_receiver(num_args: u8) -> Result<u8, RuntimeError> {
+    num_args.checked_add(1).ok_or_else(|| RuntimeError::new(ErrorKind::Type, "cannot pass more than 255 arguments to a method"))
+}
+
+/// Returns the open upvalue for stack index `slot`, creating it if no closure
+/// has captured that variable yet.
+fn capture_upvalue(open_upvalues: &mut Vec<Rc<RefCell<ObjUpvalue>>>, slot: usize) -> Rc<RefCell<ObjUpvalue>> {
+    let position = open_upvalues.partition_point(|upvalue| matches!(*upvalue.borrow(), ObjUpvalue::Open(x) if x < slot));
+    if let Some(upvalue) = open_upvalues.get(position) {
+        if matches!(*upvalue.borrow(), ObjUpvalue::Open(x) if x == slot) {
+            return upvalue.clone();
+        }
+    }
+
+    let upvalue = Rc::new(RefCell::new(ObjUpvalue::Open(slot)));
+    open_upvalues.insert(position, upvalue.clone());
+    upvalue
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{Tokenizer, parser::{Parser, Precedence}};
+    use crate::{value::Value, error::{ErrorKind, TraceFrame}};
+    use super::{VM, InterpretResult};
+
+    /// Runs each script on `vm` in turn, checking the displayed result and
+    /// that nothing is left behind on the stack.
+    fn assert_results(vm: &mut VM, cases: &[(&str, &str)]) {
+        for (source, truth) in cases {
+            match vm.interpret(source) {
+                InterpretResult::Ok(value) => assert_eq!(value.to_string(), *truth, "{}", source),
+                result => panic!("{} gave {:?}", source, result),
+            }
+            assert!(vm.stack.is_empty() && vm.frames.is_empty() && vm.open_upvalues.is_empty(), "{}", source);
+        }
+    }
+
+    /// Runs each script on `vm` in turn, checking the error it raises.
+    fn assert_errors(vm: &mut VM, cases: &[(&str, ErrorKind, &str)]) {
+        for (source, kind, message) in cases {
+            match vm.interpret(source) {
+                InterpretResult::RuntimeError(error) => assert_eq!((error.kind, error.message.as_str()), (*kind, *message), "{}", source),
+                result => panic!("{} gave {:?}", source, result),
+            }
+        }
+    }
 
     #[test]
     fn test_arithmetic() {
-    //     let tokens = Tokenizer::new(r"1 + 2").tokenize();
-    //     let mut parser = &Parser::<>::new(tokens);
-    //     parser.parse_precedence(Precedence::Assignment)
+        let result = VM::new().interpret(r"1 + 2");
+        assert!(matches!(result, InterpretResult::Ok(Value::Integer(3))));
+
+        let result = VM::new().interpret(r"(True + 1) * 2 ** True - False");
+        assert!(matches!(result, InterpretResult::Ok(Value::Integer(4))));
+        let result = VM::new().interpret(r"1 / False");
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.kind == ErrorKind::ZeroDivision));
+    }
+
+    #[test]
+    fn test_result_is_last_expression() {
+        let result = VM::new().interpret("1 + 2\n2.0 * 4.0\n");
+        assert!(matches!(result, InterpretResult::Ok(Value::Float(x)) if x == 8.0));
+
+        let result = VM::new().interpret(r"assert True");
+        assert!(matches!(result, InterpretResult::Ok(Value::None)));
+    }
+
+    #[test]
+    fn test_errors() {
+        let result = VM::new().interpret(r"assert False");
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.kind == ErrorKind::Assertion));
+
+        let result = VM::new().interpret(r"1 +");
+        assert!(matches!(result, InterpretResult::CompileError(errors) if errors.len() == 1));
+    }
+
+    #[test]
+    fn test_strings() {
+        let mut vm = VM::new();
+        let cases = [
+            (r"'ab' + 'c' == 'abc'", "True"),
+            (r"'ab' * 2 == 'abab'", "True"),
+            (r"3 * 'ab' == 'abab'", "False"),
+            (r"'ab' * 0 == ''", "True"),
+            (r"'abc' < 'abd'", "True"),
+            (r"'b' <= 'abc'", "False"),
+            (r"'a' != 'a'", "False"),
+            (r"len('h\u00e9llo') == 5", "True"),
+            ("len('''two\nlines''') == 9", "True"),
+        ];
+        assert_results(&mut vm, &cases);
+    }
+
+    #[test]
+    fn test_string_errors() {
+        let errors = [
+            ("'a' + 1", ErrorKind::Type, "unsupported operand type(s) for +: 'str' and 'int'"),
+            ("'a' < 1", ErrorKind::Type, "'<' not supported between instances of 'str' and 'int'"),
+            ("len(1)", ErrorKind::Type, "object of type 'int' has no len()"),
+            ("len('a', 'b')", ErrorKind::Type, "len() takes 1 positional arguments but 2 were given"),
+            ("'a'()", ErrorKind::Type, "'str' object is not callable"),
+        ];
+        for error in errors {
+            assert_errors(&mut VM::new(), &[error]);
+        }
+    }
+
+    #[test]
+    fn test_chained_comparisons() {
+        let mut vm = VM::new();
+        let cases = [
+            ("1 < 2 < 3", "True"),
+            ("1 < 3 < 2", "False"),
+            ("3 > 2 == 2 >= 1.5", "True"),
+            ("1 == 1 == 1", "True"),
+            ("(1 < 2) < 3", "True"),
+            ("1 != 1 < len('a')", "False"),
+            // later links are never evaluated once one is false
+            ("2 < 1 < len(1)", "False"),
+        ];
+        assert_results(&mut vm, &cases);
+        assert_errors(&mut vm, &[("1 < 2 < len(1)", ErrorKind::Type, "object of type 'int' has no len()")]);
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let mut vm = VM::new();
+        let cases = [
+            ("0 or 'x'", "x"),
+            ("1 and 0", "0"),
+            ("None or 2 and 3", "3"),
+            ("'' and len(1)", ""),
+            ("2.5 or len(1)", "2.5"),
+            ("not 0", "True"),
+            ("not 1 == 1", "False"),
+            ("not None and 0j", "0j"),
+            ("1 < 2 and not 3 < 2", "True"),
+        ];
+        assert_results(&mut vm, &cases);
+
+        assert!(matches!(vm.interpret("assert 'x'\nassert 0.0"), InterpretResult::RuntimeError(e) if e.kind == ErrorKind::Assertion));
+    }
+
+    #[test]
+    fn test_unary_operators() {
+        let mut vm = VM::new();
+        let cases = [
+            ("-2**2", "-4"),
+            ("2**-1", "0.5"),
+            ("2**3**2", "512"),
+            ("-2*3", "-6"),
+            ("-(1 + 2j)", "(-1-2j)"),
+            ("- -3", "3"),
+            ("+-2.5", "-2.5"),
+            ("-True", "-1"),
+            ("-(-2147483647 - 1)", "2147483648"),
+            ("-fraction(1, 2)", "-1/2"),
+        ];
+        assert_results(&mut vm, &cases);
+
+        assert_errors(&mut vm, &[("+'a'", ErrorKind::Type, "bad operand type for unary +: 'str'")]);
+    }
+
+    #[test]
+    fn test_globals() {
+        let mut vm = VM::new();
+        assert!(matches!(vm.interpret("x = 1\ny = x + 2\nx = y * 2\nx"), InterpretResult::Ok(Value::Integer(6))));
+        assert!(matches!(vm.interpret("a = b = 'hi'\na + b == 'hihi'"), InterpretResult::Ok(Value::Bool(true))));
+
+        // assignments are statements without a value, and globals outlive each call
+        assert!(matches!(vm.interpret("x = x + 1"), InterpretResult::Ok(Value::None)));
+        assert!(matches!(vm.interpret("x"), InterpretResult::Ok(Value::Integer(7))));
+
+        match vm.interpret("z + 1") {
+            InterpretResult::RuntimeError(error) => assert_eq!(error.to_string().lines().last(), Some("NameError: name 'z' is not defined")),
+            result => panic!("expected runtime error, got {:?}", result),
+        }
+
+        // builtins are ordinary globals
+        assert!(matches!(vm.interpret("size = len\nlen = 3\nsize('ab') + len"), InterpretResult::Ok(Value::Integer(5))));
+    }
+
+    #[test]
+    fn test_if_statements() {
+        let source = "if x > 3:\n    y = 'big'\nelif x > 1:\n    y = 'mid'\nelse:\n    y = 'small'\ny";
+        for (x, truth) in [(5, "big"), (2, "mid"), (0, "small")] {
+            let mut vm = VM::new();
+            vm.interpret(&format!("x = {}", x));
+            match vm.interpret(source) {
+                InterpretResult::Ok(value) => assert_eq!(value.to_string(), truth),
+                result => panic!("expected {}, got {:?}", truth, result),
+            }
+        }
+
+        let mut vm = VM::new();
+        assert!(matches!(vm.interpret("if 0: x = 1\nelse: x = 2\nx"), InterpretResult::Ok(Value::Integer(2))));
+        assert!(matches!(vm.interpret("if x:\n    pass\n    if x == 2:\n        x = 3\nx"), InterpretResult::Ok(Value::Integer(3))));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_while_loops() {
+        let cases = [
+            ("i = 0\ntotal = 0\nwhile i < 5:\n    i = i + 1\n    total = total + i\ntotal", "15"),
+            ("i = 0\nwhile True:\n    i = i + 1\n    if i == 3: break\ni", "3"),
+            ("i = 0\nodd = 0\nwhile i < 6:\n    i = i + 1\n    if i // 2 * 2 == i: continue\n    odd = odd + 1\nodd", "3"),
+            ("x = 'none'\nwhile 0:\n    pass\nelse:\n    x = 'else'\nx", "else"),
+            ("x = 'none'\nwhile 1:\n    break\nelse:\n    x = 'else'\nx", "none"),
+            ("n = 0\ni = 0\nwhile i < 3:\n    i = i + 1\n    j = 0\n    while True:\n        j = j + 1\n        if j > i: break\n        n = n + 1\nn", "6"),
+        ];
+        for case in cases {
+            assert_results(&mut VM::new(), &[case]);
+        }
+
+        // blocks never give the script its result
+        let mut vm = VM::new();
+        assert!(matches!(vm.interpret("if 0: 1"), InterpretResult::Ok(Value::None)));
+    }
+
+    #[test]
+    fn test_for_loops() {
+        let cases = [
+            ("total = 0\nfor i in range(5): total = total + i\ntotal", "10"),
+            ("total = 0\nfor i in range(10, 0, -3): total = total * 100 + i\ntotal", "10070401"),
+            ("s = ''\nfor c in 'héllo':\n    if c == 'l': continue\n    s = c + s\ns", "oéh"),
+            ("n = 0\nfor x in [1, 'a', [2]]:\n    n = n + 1\n    if x == 'a': break\nelse:\n    n = 10\nn", "2"),
+            ("n = 0\nfor x in []:\n    n = 1\nelse:\n    n = 2\nn", "2"),
+            ("n = 0\nfor i in range(3):\n    for j in range(i):\n        n = n + 1\nn", "3"),
+            ("[1, 'a', [2.5, None],]", "[1, 'a', [2.5, None]]"),
+            ("[\n    range(3),\n    range(1, 9, 2),\n]", "[range(0, 3), range(1, 9, 2)]"),
+            ("len([1, 2]) + len(range(0, 10, 3)) + len(range(5, 0))", "6"),
+            ("len(range(-2147483648, 2147483647))", "4294967295"),
+        ];
+        for case in cases {
+            assert_results(&mut VM::new(), &[case]);
+        }
 
+        let mut vm = VM::new();
+        let errors = [
+            ("for x in 5: pass", ErrorKind::Type, "'int' object is not iterable"),
+            ("range(1, 2, 0)", ErrorKind::Value, "range() arg 3 must not be zero"),
+            ("range(1.5)", ErrorKind::Type, "'float' object cannot be interpreted as an integer"),
+            ("range()", ErrorKind::Type, "range() takes from 1 to 3 positional arguments but 0 were given"),
+        ];
+        assert_errors(&mut vm, &errors);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_iterator_protocol() {
+        let mut vm = VM::new();
+        vm.interpret(r"
+class Countdown:
+    def __init__(self, n):
+        self.n = n
+    def __iter__(self):
+        return self
+    def __next__(self):
+        if self.n == 0:
+            return StopIteration
+        self.n = self.n - 1
+        return self.n + 1
+class Twice:
+    def __init__(self, n):
+        self.n = n
+    def __iter__(self):
+        return Countdown(self.n * 2)
+class Evens:
+    def __contains__(self, x):
+        return x // 2 * 2 == x and 1
+class Plain: pass
+class Listed:
+    def __iter__(self):
+        return [1]
+");
+        let cases = [
+            ("total = 0\nfor i in Countdown(4): total = total * 10 + i\ntotal", "4321"),
+            ("total = 0\nfor i in Twice(2): total = total * 10 + i\ntotal", "4321"),
+            ("x = 'none'\nfor i in Countdown(2):\n    pass\nelse:\n    x = 'done'\nx", "done"),
+            ("x = 'none'\nfor i in Countdown(3):\n    if i == 2: break\nelse:\n    x = 'done'\n[i, x]", "[2, 'none']"),
+            ("n = 0\nfor i in Countdown(3):\n    for j in Countdown(i): n = n + j\nn", "10"),
+            ("def total(it):\n    t = 0\n    for x in it: t = t + x\n    return t\ntotal(Countdown(4))", "10"),
+            ("[3 in Countdown(5), 9 in Countdown(5), 2 not in Twice(1)]", "[True, False, False]"),
+            ("c = Countdown(5)\n3 in c\nt = 0\nfor x in c: t = t + x\nt", "3"),
+            ("[4 in Evens(), 3 in Evens(), 3 not in Evens()]", "[True, False, True]"),
+            ("StopIteration", "StopIteration"),
+        ];
+        assert_results(&mut vm, &cases);
+
+        let errors = [
+            ("for x in Plain(): pass", ErrorKind::Type, "'Plain' object is not iterable"),
+            ("1 in Plain()", ErrorKind::Type, "argument of type 'Plain' is not iterable"),
+            ("for x in Listed(): pass", ErrorKind::Type, "iter() returned non-iterator of type 'list'"),
+            ("1 in Listed()", ErrorKind::Type, "iter() returned non-iterator of type 'list'"),
+        ];
+        assert_errors(&mut vm, &errors);
+    }
+
+    #[test]
+    fn test_membership() {
+        let mut vm = VM::new();
+        let cases = [
+            ("'ell' in 'hello'", "True"),
+            ("'x' not in 'hello'", "True"),
+            ("2 in [1, 2.0]", "True"),
+            ("[1] in [[1], 2]", "True"),
+            ("6 in range(0, 10, 3)", "True"),
+            ("7 in range(0, 10, 3)", "False"),
+            ("-2.0 in range(0, -5, -2)", "True"),
+            ("10 in range(0, 10)", "False"),
+            ("not 1 in [1]", "False"),
+            ("1 in [1] in [[1]]", "True"),
+            ("1 in [1] in [True]", "False"),
+            ("[1, 'a'] == [1.0, 'a'] and range(0) == range(3, 1)", "True"),
+        ];
+        assert_results(&mut vm, &cases);
+
+        let errors = [
+            ("1 in 'abc'", ErrorKind::Type, "'in <string>' requires string as left operand, not int"),
+            ("1 not in 2", ErrorKind::Type, "argument of type 'int' is not iterable"),
+        ];
+        assert_errors(&mut vm, &errors);
+    }
+
+    #[test]
+    fn test_conditional_expressions() {
+        let mut vm = VM::new();
+        let cases = [
+            ("1 if True else 2", "1"),
+            ("1 / 0 if False else 'safe'", "safe"),
+            ("'a' if 0 else 'b' if 0 else 'c'", "c"),
+            ("x = 0\n(1 / x if x else 0) + 1", "1"),
+            ("y = 3 if 1 < 2 < 3 else 4\ny", "3"),
+            ("1 if 0 else 2 or 3", "2"),
+        ];
+        assert_results(&mut vm, &cases);
+    }
+
+    #[test]
+    fn test_fractions() {
+        let mut vm = VM::new();
+        assert!(matches!(vm.interpret("fraction(1, 3) + fraction(1, 6) == fraction(2, 4)"), InterpretResult::Ok(Value::Bool(true))));
+        assert!(matches!(vm.interpret("fraction(fraction(1, 2), 3) < fraction(1, 5)"), InterpretResult::Ok(Value::Bool(true))));
+        assert!(matches!(vm.interpret("fraction(True, 2) == 0.5"), InterpretResult::Ok(Value::Bool(true))));
+
+        match vm.interpret("fraction(1, 0)") {
+            InterpretResult::RuntimeError(error) => assert_eq!(error.to_string().lines().last(), Some("ZeroDivisionError: Fraction(1, 0)")),
+            result => panic!("expected runtime error, got {:?}", result),
+        }
+        match vm.interpret("fraction(1.5, 2)") {
+            InterpretResult::RuntimeError(error) => assert_eq!(error.kind, ErrorKind::Type),
+            result => panic!("expected runtime error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_runtime_error_traceback() {
+        let mut vm = VM::new();
+        let error = match vm.interpret("1 + 2\n1 // 0") {
+            InterpretResult::RuntimeError(error) => error,
+            result => panic!("expected runtime error, got {:?}", result),
+        };
+        assert_eq!(error.kind, ErrorKind::ZeroDivision);
+        assert_eq!(error.traceback, vec![TraceFrame { function: String::from("<script>"), line: 2 }]);
+        assert_eq!(error.to_string(), "Traceback (most recent call last):\n  line 2, in <script>\nZeroDivisionError: integer division by zero");
+
+        // the VM is left usable after an error
+        assert!(matches!(vm.interpret("2 * 3"), InterpretResult::Ok(Value::Integer(6))));
+    }
+
+    #[test]
+    fn test_functions() {
+        let cases = [
+            ("def add(a, b): return a + b\nadd(1, 2)", "3"),
+            ("def f():\n    x = 1\nf()", "None"),
+            ("def fib(n):\n    if n < 2: return n\n    return fib(n - 1) + fib(n - 2)\nfib(15)", "610"),
+            ("def total(xs):\n    t = 0\n    for x in xs:\n        if x > 3: break\n        t = t + x\n    return t\ntotal(range(10))", "6"),
+            ("scale = 10\ndef f(x):\n    def g(y): return y * scale\n    return g(x) + 1\nf(2)", "21"),
+            ("def f(): return\nx = f()\nf", "<function f>"),
+            ("def f(a, b,):\n    a = a * 2\n    return [a, b]\nf('x', 0)", "['xx', 0]"),
+        ];
+        for case in cases {
+            assert_results(&mut VM::new(), &[case]);
+        }
+
+        assert_errors(&mut VM::new(), &[("def f(a): return a\nf(1, 2)", ErrorKind::Type, "f() takes 1 positional arguments but 2 were given")]);
+    }
+
+    #[test]
+    fn test_closures() {
+        let cases = [
+            (
+                "def counter():\n    n = 0\n    def inc():\n        nonlocal n\n        n = n + 1\n        return n\n    return inc\nc = counter()\nc()\nc()\nd = counter()\nd()\n[c(), d()]",
+                "[3, 2]",
+            ),
+            (
+                "def make():\n    global get, put\n    x = 1\n    def get(): return x\n    def put(v):\n        nonlocal x\n        x = v\n    put(5)\n    return get()\nbefore = make()\nput(7)\n[before, get()]",
+                "[5, 7]",
+            ),
+            ("def a():\n    x = 'a'\n    def b():\n        def c(): return x + '!'\n        return c\n    return b()()\na()", "a!"),
+            ("total = 0\ndef add(n):\n    global total\n    total = total + n\nadd(2)\nadd(3)\ntotal", "5"),
+            (
+                "def f():\n    g = None\n    for i in range(3):\n        def h(): return i\n        if i == 1: g = h\n    return g()\nf()",
+                "1",
+            ),
+            (
+                "def f():\n    g = None\n    for i in range(5):\n        def h(): return i * 10\n        g = h\n        if i == 2: break\n    return g()\nf()",
+                "20",
+            ),
+            (
+                "def outer():\n    def fib(n):\n        if n < 2: return n\n        return fib(n-1) + fib(n-2)\n    return fib(10)\nouter()",
+                "55",
+            ),
+        ];
+        for case in cases {
+            assert_results(&mut VM::new(), &[case]);
+        }
+    }
+
+    #[test]
+    fn test_classes() {
+        let mut vm = VM::new();
+        vm.interpret(r"
+class Point:
+    dims = 2
+    def __init__(self, x, y):
+        self.x = x
+        self.y = y
+    def norm2(self):
+        return self.x * self.x + self.y * self.y
+p = Point(3, 4)
+");
+        let cases = [
+            ("[p.norm2(), p.x, Point.dims, p.dims]", "[25, 3, 2, 2]"),
+            ("m = p.norm2\np.x = 0\nm()", "16"),
+            ("p.y = 1", "None"),
+            ("[Point, p, p.norm2]", "[<class 'Point'>, <Point object>, <bound method norm2 of <Point object>>]"),
+            ("p.norm2 = 'field'\np.norm2", "field"),
+            ("Point.norm2(Point(1, 1))", "2"),
+            ("class E: pass\ne = E()\ne.a = [1]\ne.a", "[1]"),
+            (
+                "class Counter:\n    def __init__(self): self.n = 0\n    def inc(self):\n        self.n = self.n + 1\n        return self\nCounter().inc().inc().n",
+                "2",
+            ),
+            ("def make(v):\n    class Box:\n        def get(self): return v\n    return Box()\nmake(5).get()", "5"),
+            ("def make():\n    class A:\n        def clone(self): return A()\n    return A().clone()\nmake()", "<A object>"),
+            ("def make():\n    class B(E):\n        def parent(self): return B\n    return B().parent()\nmake()", "<class 'B'>"),
+        ];
+        assert_results(&mut vm, &cases);
+
+        let errors = [
+            ("p.z", ErrorKind::Attribute, "'Point' object has no attribute 'z'"),
+            ("Point.z", ErrorKind::Attribute, "type object 'Point' has no attribute 'z'"),
+            ("(1).x = 2", ErrorKind::Attribute, "'int' object has no attribute 'x'"),
+            ("Point(1)", ErrorKind::Type, "__init__() takes 3 positional arguments but 2 were given"),
+            ("E(1)", ErrorKind::Type, "E() takes no arguments"),
+            ("1 + p", ErrorKind::Type, "unsupported operand type(s) for +: 'int' and 'Point'"),
+        ];
+        assert_errors(&mut vm, &errors);
+    }
+
+    #[test]
+    fn test_inheritance() {
+        let mut vm = VM::new();
+        vm.interpret(r"
+class Animal:
+    legs = 4
+    def __init__(self, name):
+        self.name = name
+    def speak(self):
+        return self.name + ' makes a sound'
+    def describe(self):
+        return self.speak() + ' on ' + self.legs * 'I'
+class Dog(Animal):
+    def speak(self):
+        return self.name + ' barks'
+class Bird(Animal):
+    legs = 2
+    def __init__(self, name, song):
+        super().__init__(name)
+        self.song = song
+    def speak(self):
+        return super().speak() + ' and ' + self.song
+class Parrot(Bird):
+    def speak(self):
+        return 'Polly says: ' + super().speak()
+");
+        let cases = [
+            ("Dog('rex').describe()", "rex barks on IIII"),
+            ("Bird('tweety', 'sings').describe()", "tweety makes a sound and sings on II"),
+            ("Parrot('polly', 'squawks').speak()", "Polly says: polly makes a sound and squawks"),
+            ("Animal.fur = True\nParrot.fur", "True"),
+            ("class Empty(): pass\nEmpty()", "<Empty object>"),
+            ("def make(base):\n    class Sub(base):\n        def describe(self): return 'sub ' + super().describe()\n    return Sub\nmake(Dog)('max').describe()", "sub max barks on IIII"),
+        ];
+        assert_results(&mut vm, &cases);
+
+        let errors = [
+            ("class Bad(1): pass", ErrorKind::Type, "cannot inherit from 'int' object"),
+            ("class A:\n    def f(self): return super().f()\nA().f()", ErrorKind::Attribute, "'super' object has no attribute 'f'"),
+            ("Dog('rex').fly()", ErrorKind::Attribute, "'Dog' object has no attribute 'fly'"),
+        ];
+        assert_errors(&mut vm, &errors);
+    }
+
+    #[test]
+    fn test_operator_overloading() {
+        let mut vm = VM::new();
+        vm.interpret(r"
+class Vector:
+    def __init__(self, x, y):
+        self.x = x
+        self.y = y
+    def __add__(self, other):
+        return Vector(self.x + other.x, self.y + other.y)
+    def __sub__(self, other):
+        return Vector(self.x - other.x, self.y - other.y)
+    def __mul__(self, k):
+        return Vector(self.x * k, self.y * k)
+    def __rmul__(self, k):
+        return self * k
+    def __truediv__(self, k):
+        return Vector(self.x / k, self.y / k)
+    def __rtruediv__(self, k):
+        return 'reflected ' + k
+    def __floordiv__(self, k):
+        return Vector(self.x // k, self.y // k)
+    def __pow__(self, k):
+        return self.x ** k + self.y ** k
+    def __radd__(self, k):
+        return Vector(self.x + k, self.y + k)
+    def __rsub__(self, k):
+        return Vector(k - self.x, k - self.y)
+    def __neg__(self):
+        return Vector(-self.x, -self.y)
+    def __eq__(self, other):
+        return self.x == other.x and self.y == other.y
+    def __lt__(self, other):
+        return self.x ** 2 + self.y ** 2 < other.x ** 2 + other.y ** 2
+    def show(self):
+        return '(' + self.x * 'x' + ', ' + self.y * 'y' + ')'
+class Labelled(Vector):
+    def __add__(self, other):
+        return 'labelled ' + super().__add__(other).show()
+v = Vector(1, 2)
+w = Vector(3, 1)
+");
+        let cases = [
+            ("(v + w).show()", "(xxxx, yyy)"),
+            ("(w - v).show()", "(xx, )"),
+            ("(v * 2).show()", "(xx, yyyy)"),
+            ("(3 * v).show()", "(xxx, yyyyyy)"),
+            ("(1 + v).show()", "(xx, yyy)"),
+            ("(5 - v).show()", "(xxxx, yyy)"),
+            ("(Vector(4, 6) / 2).x", "2.0"),
+            ("'a' / v", "reflected a"),
+            ("(Vector(5, 7) // 2).show()", "(xx, yyy)"),
+            ("v ** 2", "5"),
+            ("(-(-v)).show()", "(x, yy)"),
+            ("v == Vector(1, 2)", "True"),
+            ("v != Vector(1, 2)", "False"),
+            ("v != w", "True"),
+            ("v < w", "True"),
+            ("w > v", "True"),
+            ("v < w < Vector(9, 9)", "True"),
+            ("Labelled(1, 1) + v", "labelled (xx, yyy)"),
+            ("v + Labelled(1, 1)", "<Vector object>"),
+            ("class Plain: pass\np = Plain()\np == p", "True"),
+            ("Plain() == Plain()", "False"),
+        ];
+        assert_results(&mut vm, &cases);
+
+        let errors = [
+            ("v <= w", ErrorKind::Type, "'<=' not supported between instances of 'Vector' and 'Vector'"),
+            ("+v", ErrorKind::Type, "bad operand type for unary +: 'Vector'"),
+            ("v + 1", ErrorKind::Attribute, "'int' object has no attribute 'x'"),
+            ("Plain() * 2", ErrorKind::Type, "unsupported operand type(s) for *: 'Plain' and 'int'"),
+        ];
+        assert_errors(&mut vm, &errors);
+
+        match vm.interpret("class Loop:\n    def __add__(self, other): return self + other\nLoop() + 1") {
+            InterpretResult::RuntimeError(error) => {
+                assert_eq!(error.kind, ErrorKind::Recursion);
+                assert_eq!(error.traceback.len(), super::DEFAULT_FRAME_LIMIT);
+            }
+            result => panic!("unbounded __add__ gave {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_special_method_equality() {
+        let mut vm = VM::new();
+        vm.interpret(r"
+class Money:
+    def __init__(self, amount):
+        self.amount = amount
+    def __eq__(self, other):
+        return self.amount == other
+class Shy:
+    def __add__(self, other):
+        return NotImplemented
+    def __eq__(self, other):
+        return NotImplemented
+class Cents:
+    def __radd__(self, other):
+        return 'cents'
+class Wallet:
+    def __init__(self):
+        self.left = 3
+    def __iter__(self):
+        return self
+    def __next__(self):
+        self.left = self.left - 1
+        if self.left < 0: return StopIteration
+        return Money(self.left)
+purse = [Money(1), Money(5)]
+");
+        let cases = [
+            ("NotImplemented", "NotImplemented"),
+            ("5 in purse", "True"),
+            ("3 in purse", "False"),
+            ("Money(5) in purse", "True"),
+            ("1 in Wallet()", "True"),
+            ("4 in Wallet()", "False"),
+            ("purse == [Money(1), 5]", "True"),
+            ("purse == [Money(1), Money(4)]", "False"),
+            ("purse != [1, 5]", "False"),
+            ("purse == [Money(1)]", "False"),
+            ("[[Money(1)], 2] == [[1], 2]", "True"),
+            ("Money(1) == 'one'", "False"),
+            ("Money(1) != 'one'", "True"),
+            ("Shy() + Cents()", "cents"),
+            ("Shy() == Shy()", "False"),
+            ("[Shy()] == [Shy()]", "False"),
+            ("s = Shy()\n[s] == [s]", "True"),
+            ("s in [Shy(), s]", "True"),
+        ];
+        assert_results(&mut vm, &cases);
+
+        let errors = [
+            ("Shy() + 1", ErrorKind::Type, "unsupported operand type(s) for +: 'Shy' and 'int'"),
+            ("Shy() + Shy()", ErrorKind::Type, "unsupported operand type(s) for +: 'Shy' and 'Shy'"),
+        ];
+        assert_errors(&mut vm, &errors);
+    }
+
+    #[test]
+    fn test_frame_limit() {
+        let source = "def down(n):\n    if n == 0: return 0\n    return down(n - 1)\n";
+        let mut vm = VM::new().frame_limit(10);
+        vm.interpret(source);
+        assert!(matches!(vm.interpret("down(8)"), InterpretResult::Ok(Value::Integer(0))));
+
+        let error = match vm.interpret("down(9)") {
+            InterpretResult::RuntimeError(error) => error,
+            result => panic!("expected a RecursionError, got {:?}", result),
+        };
+        assert_eq!(error.kind, ErrorKind::Recursion);
+        assert_eq!(error.traceback.len(), 10);
+        assert_eq!(error.traceback[1], TraceFrame { function: String::from("down"), line: 3 });
+
+        // the default is deep enough for ordinary recursion
+        let mut vm = VM::new();
+        vm.interpret(source);
+        assert!(matches!(vm.interpret("down(900)"), InterpretResult::Ok(Value::Integer(0))));
+    }
+}